@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use identicon_generator::grid::Grid;
+use identicon_generator::render::{self, Layout};
+use identicon_generator::shape::Shape;
+use identicon_generator::{Format, Identicon, IdenticonBuilder};
+use image::{DynamicImage, GenericImage, Rgba, RgbaImage};
+
+const RESOLUTIONS: [u32; 3] = [200, 500, 1000];
+
+fn identicon(resolution: u32, shape: Shape) -> Identicon {
+    IdenticonBuilder::new()
+        .grid_size(5)
+        .resolution(resolution)
+        .shape(shape)
+        .format(Format::Png)
+        .build()
+        .unwrap()
+}
+
+/// The previous renderer, which wrote every pixel of every cell through
+/// `DynamicImage::put_pixel`, for comparison.
+fn render_per_pixel(layout: &Layout, palette: &[Rgba<u8>], background: Rgba<u8>, grid: &Grid) -> DynamicImage {
+    let (cell_width, cell_height) = layout.cell_size();
+    let (width, height) = layout.canvas_size();
+    let mut img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, background));
+    for y in 0..grid.rows() {
+        for x in 0..grid.columns() {
+            let value = grid.cell(i64::from(x), i64::from(y)).unwrap_or(0);
+            if let Some(&fill_color) = palette.get(usize::from(value).wrapping_sub(1)) {
+                let (px, py) = layout.cell_origin(x, y);
+                for py in py as u32..py as u32 + cell_height {
+                    for px in px as u32..px as u32 + cell_width {
+                        img.put_pixel(px, py, fill_color);
+                    }
+                }
+            }
+        }
+    }
+    img
+}
+
+fn rasterise(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rasterise");
+    let palette = [Rgba([200, 100, 50, 255])];
+    let background = Rgba([0, 0, 0, 0]);
+
+    for &resolution in &RESOLUTIONS {
+        let identicon = identicon(resolution, Shape::Square);
+        let grid = identicon.grid("xoltia").unwrap();
+        let layout = identicon.layout();
+
+        group.bench_with_input(BenchmarkId::new("scanline", resolution), &grid, |b, grid| {
+            b.iter(|| render::render(layout, &palette, background, grid))
+        });
+        group.bench_with_input(BenchmarkId::new("per_pixel", resolution), &grid, |b, grid| {
+            b.iter(|| render_per_pixel(layout, &palette, background, grid))
+        });
+    }
+
+    group.finish();
+}
+
+fn shapes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shapes");
+    for &shape in &[Shape::Rounded, Shape::Circle, Shape::Diamond] {
+        let identicon = identicon(1000, shape);
+        group.bench_function(format!("{:?}", shape).to_lowercase(), |b| {
+            b.iter(|| identicon.image("xoltia").unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_png");
+    for &resolution in &RESOLUTIONS {
+        let identicon = identicon(resolution, Shape::Square);
+        group.bench_with_input(BenchmarkId::from_parameter(resolution), &identicon, |b, identicon| {
+            b.iter(|| identicon.encode("xoltia").unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rasterise, shapes, encode);
+criterion_main!(benches);
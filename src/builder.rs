@@ -0,0 +1,519 @@
+use image::{DynamicImage, Rgba};
+
+use crate::color::{self, Background, Bounds, ColorModel, ColorOptions, Contrast};
+use crate::encode::{self, Format};
+use crate::grid::{self, Grid, Symmetry};
+use crate::render::{self, Filter, Gap, Insets, Layout, Length, Padding};
+use crate::shape::{Mask, Shape};
+use crate::hash::{self, Algorithm, Key};
+use crate::normalize::{self, Case, Normalization};
+use crate::{closest_multiple, svg, Error, MAX_RESOLUTION};
+
+/// Configures an [`Identicon`] generator.
+///
+/// ```
+/// use identicon_generator::{Format, IdenticonBuilder};
+///
+/// let identicon = IdenticonBuilder::new()
+///     .grid_size(5)
+///     .resolution(250)
+///     .format(Format::Png)
+///     .build()
+///     .unwrap();
+/// let png = identicon.encode("xoltia").unwrap();
+/// assert!(!png.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct IdenticonBuilder {
+    columns: Option<u32>,
+    rows: u32,
+    padding: Padding,
+    gap: Gap,
+    width: Option<u32>,
+    height: Option<u32>,
+    symmetry: Symmetry,
+    shape: Shape,
+    mask: Mask,
+    filter: Filter,
+    normalization: Normalization,
+    algorithm: Algorithm,
+    key: Option<Key>,
+    colors: u8,
+    color_options: ColorOptions,
+    background: Background,
+    contrast: Contrast,
+    format: Format,
+}
+
+impl IdenticonBuilder {
+    pub fn new() -> Self {
+        IdenticonBuilder {
+            columns: None,
+            rows: 5,
+            padding: Padding::default(),
+            gap: Gap::default(),
+            width: None,
+            height: None,
+            symmetry: Symmetry::default(),
+            shape: Shape::default(),
+            mask: Mask::default(),
+            filter: Filter::default(),
+            normalization: Normalization::default(),
+            algorithm: Algorithm::default(),
+            key: None,
+            colors: 2,
+            color_options: ColorOptions::default(),
+            background: Background::default(),
+            contrast: Contrast::default(),
+            format: Format::Png,
+        }
+    }
+
+    /// Number of cells along each side. Defaults to 5.
+    ///
+    /// On a canvas whose [`width`](IdenticonBuilder::width) and
+    /// [`height`](IdenticonBuilder::height) differ, this is the number of
+    /// rows and the columns follow the aspect ratio, keeping the cells
+    /// roughly square.
+    pub fn grid_size(mut self, grid_size: u32) -> Self {
+        self.columns = None;
+        self.rows = grid_size;
+        self
+    }
+
+    /// Number of cells along the horizontal axis, overriding the one implied
+    /// by the [`grid_size`](IdenticonBuilder::grid_size).
+    pub fn columns(mut self, columns: u32) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// Number of cells along the vertical axis. Same as the
+    /// [`grid_size`](IdenticonBuilder::grid_size), except that explicitly
+    /// set columns are kept.
+    pub fn rows(mut self, rows: u32) -> Self {
+        self.rows = rows;
+        self
+    }
+
+    /// Blank border around the grid, either the same number of pixels on
+    /// every side or a [`Padding`] per side. Defaults to 0.
+    ///
+    /// The padded canvas is limited to [`MAX_RESOLUTION`] pixels per side.
+    pub fn padding<P: Into<Padding>>(mut self, padding: P) -> Self {
+        self.padding = padding.into();
+        self
+    }
+
+    /// Gutter between neighbouring cells. Defaults to none, so neighbouring
+    /// square cells merge.
+    ///
+    /// Fractions are of the shorter side of the cells, so the gutter is as
+    /// wide between columns as between rows.
+    pub fn gap(mut self, gap: Gap) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Width and height of the grid in pixels. Defaults to square cells of
+    /// the size that brings the longer side closest to 200.
+    ///
+    /// Sides that are not a multiple of the number of cells along them are
+    /// resampled with the [`filter`](IdenticonBuilder::filter).
+    pub fn resolution(mut self, resolution: u32) -> Self {
+        self.width = Some(resolution);
+        self.height = Some(resolution);
+        self
+    }
+
+    /// Width of the grid in pixels. Without a height, the height is chosen
+    /// to keep the cells square.
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Height of the grid in pixels. Without a width, the width is chosen
+    /// to keep the cells square.
+    pub fn height(mut self, height: u32) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// Shorthand for a [`symmetry`](IdenticonBuilder::symmetry) of
+    /// [`Symmetry::Horizontal`] or [`Symmetry::None`].
+    pub fn symmetrical(mut self, symmetrical: bool) -> Self {
+        self.symmetry = if symmetrical { Symmetry::Horizontal } else { Symmetry::None };
+        self
+    }
+
+    /// Which cells repeat each other. Defaults to [`Symmetry::Horizontal`].
+    ///
+    /// Repeated cells share digest bits, so stronger symmetries need fewer
+    /// of them.
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    /// Outline of the filled cells. Defaults to [`Shape::Square`].
+    pub fn shape(mut self, shape: Shape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Outline the whole canvas is clipped to. Defaults to [`Mask::None`].
+    ///
+    /// Formats without an alpha channel show the background colour outside
+    /// of the mask.
+    pub fn mask(mut self, mask: Mask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Resampling filter for grid sides that are not a multiple of the
+    /// number of cells along them. Defaults to [`Filter::Box`].
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Case folding applied to names. Defaults to [`Case::Preserve`].
+    pub fn case(mut self, case: Case) -> Self {
+        self.normalization.case = case;
+        self
+    }
+
+    /// Whether surrounding whitespace is stripped from names. Defaults to
+    /// `false`.
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.normalization.trim = trim;
+        self
+    }
+
+    /// Hash function the name is digested with. Defaults to
+    /// [`Algorithm::Sized`].
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Secret the name is keyed with before hashing. Defaults to none.
+    ///
+    /// See [`hash::keyed`].
+    pub fn key(mut self, key: Key) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Number of palette entries, counting the background, in range 2-4.
+    /// Defaults to 2, a single fill colour.
+    ///
+    /// Half of the cells stay empty whatever the number of colours, and the
+    /// fill colours share the rest about evenly. Cells take 1, 2 and 7
+    /// digest bits for 2, 3 and 4 colours.
+    pub fn colors(mut self, colors: u8) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// How digest bytes become colours. Defaults to [`ColorModel::Hsl`].
+    pub fn color_model(mut self, model: ColorModel) -> Self {
+        self.color_options.model = model;
+        self
+    }
+
+    /// Saturation range of the HSL colour model.
+    pub fn saturation(mut self, saturation: Bounds) -> Self {
+        self.color_options.saturation = saturation;
+        self
+    }
+
+    /// Lightness range of the HSL colour model.
+    pub fn lightness(mut self, lightness: Bounds) -> Self {
+        self.color_options.lightness = lightness;
+        self
+    }
+
+    /// Replaces the colour model together with both of its ranges.
+    pub fn color_options(mut self, options: ColorOptions) -> Self {
+        self.color_options = options;
+        self
+    }
+
+    /// Colour of the canvas behind the cells. Defaults to transparent.
+    ///
+    /// Formats without an alpha channel are composited onto this colour,
+    /// or onto white while it is transparent.
+    pub fn background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Minimum WCAG contrast of the fill colours against the background.
+    /// Defaults to [`Contrast::Off`].
+    ///
+    /// See [`color::ensure_contrast`] for how colours are adjusted, and why
+    /// [`Contrast::Aaa`] cannot be met on a transparent background.
+    pub fn contrast(mut self, contrast: Contrast) -> Self {
+        self.contrast = contrast;
+        self
+    }
+
+    /// Format used by [`Identicon::encode`]. Defaults to PNG.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Validates the configuration.
+    pub fn build(self) -> Result<Identicon, Error> {
+        let rows = self.rows;
+        let columns = match (self.columns, self.width, self.height) {
+            (Some(columns), _, _) => columns,
+            (None, Some(width), Some(height)) if height > 0 => {
+                ((f64::from(rows) * f64::from(width) / f64::from(height)).round() as u32).max(1)
+            }
+            _ => rows,
+        };
+        if rows == 0 || columns == 0 {
+            return Err(Error::GridSize(0));
+        }
+
+        let scaled = |pixels: u32, from: u32, to: u32| {
+            (f64::from(pixels) * f64::from(to) / f64::from(from)).round() as u32
+        };
+        let (width, height) = match (self.width, self.height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, scaled(width, columns, rows)),
+            (None, Some(height)) => (scaled(height, rows, columns), height),
+            (None, None) => {
+                let longest = columns.max(rows);
+                let cell = closest_multiple(200, longest) / longest;
+                (columns * cell, rows * cell)
+            }
+        };
+        let layout = Layout {
+            columns,
+            rows,
+            padding: self.padding.resolve(width, height),
+            gap: self.gap.resolve(width, columns).min(self.gap.resolve(height, rows)),
+            width,
+            height,
+            symmetry: self.symmetry,
+            shape: self.shape,
+            mask: self.mask,
+        };
+
+        let Insets { top, right, bottom, left } = layout.padding;
+        let (side, padding) = [(width, left.saturating_add(right)), (height, top.saturating_add(bottom))]
+            .iter()
+            .copied()
+            .max_by_key(|&(side, padding)| side.saturating_add(padding))
+            .unwrap();
+        if side.saturating_add(padding) > MAX_RESOLUTION {
+            return Err(Error::ResolutionTooLarge { side, padding });
+        }
+        if side.saturating_add(padding) > 256 && self.format == Format::Ico {
+            return Err(Error::IcoTooLarge { side, padding });
+        }
+
+        match (Layout { gap: 0, ..layout }).cell_size() {
+            (0, _) => return Err(Error::GridLargerThanResolution { grid_size: columns, resolution: width }),
+            (_, 0) => return Err(Error::GridLargerThanResolution { grid_size: rows, resolution: height }),
+            _ => {}
+        }
+
+        if let (0, _) | (_, 0) = layout.cell_size() {
+            return Err(Error::GapTooLarge(layout.gap));
+        }
+
+        if !(2..=4).contains(&self.colors) {
+            return Err(Error::ColorCount(self.colors));
+        }
+
+        Ok(Identicon {
+            layout,
+            filter: self.filter,
+            normalization: self.normalization,
+            algorithm: self.algorithm,
+            key: self.key,
+            colors: self.colors,
+            color_options: self.color_options,
+            background: self.background,
+            contrast: self.contrast,
+            format: self.format,
+        })
+    }
+}
+
+impl Default for IdenticonBuilder {
+    fn default() -> Self {
+        IdenticonBuilder::new()
+    }
+}
+
+/// A validated identicon configuration, reusable across names.
+#[derive(Debug, Clone)]
+pub struct Identicon {
+    layout: Layout,
+    filter: Filter,
+    normalization: Normalization,
+    algorithm: Algorithm,
+    key: Option<Key>,
+    colors: u8,
+    color_options: ColorOptions,
+    background: Background,
+    contrast: Contrast,
+    format: Format,
+}
+
+impl Identicon {
+    /// The grid layout this identicon renders with.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// The format used by [`Identicon::encode`].
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The name of the hash function names are digested with, see
+    /// [`Algorithm::name_for`].
+    pub fn hash_algorithm(&self) -> &'static str {
+        self.algorithm.name_for(self.grid_size())
+    }
+
+    /// The grid size the digest is sized for.
+    fn grid_size(&self) -> u32 {
+        self.layout.columns.max(self.layout.rows)
+    }
+
+    /// A description of everything that determines the output for `name`.
+    /// Equal keys encode to the same bytes, however the parameters and the
+    /// name were spelled.
+    ///
+    /// The key outlives the process in caches, so it is spelled out field by
+    /// field, and fractions by their bits. The secret of the key is covered
+    /// by its [fingerprint](Key::fingerprint).
+    pub fn cache_key(&self, name: &str) -> String {
+        let Layout { columns, rows, padding, gap, width, height, symmetry, shape, mask } = self.layout;
+        let Insets { top, right, bottom, left } = padding;
+        let ColorOptions { model, saturation, lightness } = self.color_options;
+        let bits = |fraction: f32| format!("{:08x}", fraction.to_bits());
+        let mask = match mask {
+            Mask::None => "none".to_owned(),
+            Mask::Circle => "circle".to_owned(),
+            Mask::Squircle => "squircle".to_owned(),
+            Mask::Rounded(Length::Pixels(pixels)) => format!("rounded:{}px", pixels),
+            Mask::Rounded(Length::Percent(percent)) => format!("rounded:{}%", bits(percent)),
+        };
+        let key = match &self.key {
+            Some(key) => format!("{}:{}", key.version(), key.fingerprint()),
+            None => "none".to_owned(),
+        };
+        let background = match self.background {
+            Background::Color(Rgba([r, g, b, a])) => format!("{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+            Background::Auto => "auto".to_owned(),
+        };
+
+        [
+            format!("format={}", self.format.extension()),
+            format!("grid={}x{}", columns, rows),
+            format!("size={}x{}", width, height),
+            format!("pad={},{},{},{}", top, right, bottom, left),
+            format!("gap={}", gap),
+            format!("sym={}", symmetry.name()),
+            format!("shape={}", shape.name()),
+            format!("mask={}", mask),
+            format!("filter={}", self.filter.name()),
+            format!("algo={}", self.algorithm.name()),
+            format!("key={}", key),
+            format!("colors={}", self.colors),
+            format!("model={}", model.name()),
+            format!("sat={}-{}", bits(saturation.min), bits(saturation.max)),
+            format!("light={}-{}", bits(lightness.min), bits(lightness.max)),
+            format!("bg={}", background),
+            format!("contrast={}", self.contrast.name()),
+            // Last, as the name may contain any character.
+            format!("name={}", normalize::normalize(name, &self.normalization)),
+        ]
+        .join(";")
+    }
+
+    fn derive(&self, name: &str) -> Result<Derived, Error> {
+        let Layout { columns, rows, symmetry, .. } = self.layout;
+        let cell_bits = symmetry.free_cells(columns, rows) * grid::bits_per_cell(self.colors);
+        let grid_size = self.grid_size();
+        let len = color::palette_bytes(self.colors) + cell_bits.div_ceil(8);
+
+        let name = normalize::normalize(name, &self.normalization);
+        let name = name.as_str();
+        let digest = match &self.key {
+            Some(key) => hash::digest(&hash::keyed(name, key), self.algorithm, grid_size, len),
+            None => hash::digest(name.as_bytes(), self.algorithm, grid_size, len),
+        };
+        let mut palette = color::palette(&digest, self.colors, &self.color_options).ok_or(Error::ExhaustedBits)?;
+        let background = self.background.resolve(palette[0]);
+        for fill_color in &mut palette {
+            *fill_color = color::ensure_contrast(*fill_color, background, self.contrast);
+        }
+        Ok(Derived {
+            digest,
+            palette,
+            background,
+        })
+    }
+
+    fn assign(&self, derived: &Derived) -> Result<Grid, Error> {
+        let bits = hash::bits(&derived.digest[color::palette_bytes(self.colors)..]);
+        let values = grid::cell_values(bits, self.colors);
+        Grid::from_cells(self.layout.columns, self.layout.rows, self.layout.symmetry, values)
+    }
+
+    /// Assigns the cells of the identicon for `name`.
+    pub fn grid(&self, name: &str) -> Result<Grid, Error> {
+        self.assign(&self.derive(name)?)
+    }
+
+    /// Renders the identicon for `name`.
+    pub fn image(&self, name: &str) -> Result<DynamicImage, Error> {
+        let derived = self.derive(name)?;
+        let grid = self.assign(&derived)?;
+        Ok(render::render_scaled(&self.layout, &derived.palette, derived.background, &grid, self.filter))
+    }
+
+    /// Draws the identicon for `name` as an SVG document.
+    pub fn svg(&self, name: &str) -> Result<String, Error> {
+        let derived = self.derive(name)?;
+        let grid = self.assign(&derived)?;
+        Ok(svg::document(&self.layout, &grid, &derived.palette, derived.background))
+    }
+
+    /// Renders the identicon for `name` and encodes it.
+    ///
+    /// Formats without an alpha channel are composited onto the background.
+    pub fn encode(&self, name: &str) -> Result<Vec<u8>, Error> {
+        if self.format.is_vector() {
+            return self.svg(name).map(String::into_bytes);
+        }
+
+        let derived = self.derive(name)?;
+        let grid = self.assign(&derived)?;
+        let img = render::render_scaled(&self.layout, &derived.palette, derived.background, &grid, self.filter);
+        if self.format.supports_alpha() {
+            encode::encode(&img, self.format)
+        } else {
+            let matte = color::blend(derived.background, Rgba([255, 255, 255, 255]));
+            encode::encode(&encode::flatten(&img, matte), self.format)
+        }
+    }
+}
+
+/// Everything derived from the digest of a single name.
+struct Derived {
+    digest: Vec<u8>,
+    palette: Vec<Rgba<u8>>,
+    background: Rgba<u8>,
+}
@@ -0,0 +1,456 @@
+//! Colour derivation from the digest.
+
+use std::str::FromStr;
+
+use image::Rgba;
+
+use crate::Error;
+
+/// Number of leading digest bytes consumed by [`fill_color`].
+pub const COLOR_BYTES: usize = 3;
+
+/// Builds the opaque fill colour from the first three bytes of the digest.
+///
+/// Returns `None` if the digest is shorter than [`COLOR_BYTES`].
+pub fn fill_color(digest: &[u8]) -> Option<Rgba<u8>> {
+    match digest {
+        [r, g, b, ..] => Some(Rgba([*r, *g, *b, 255])),
+        _ => None,
+    }
+}
+
+/// How digest bytes are turned into colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorModel {
+    /// The bytes are used as the red, green and blue channels directly.
+    Raw,
+    /// The bytes pick a hue, with saturation and lightness kept in bounds.
+    #[default]
+    Hsl,
+}
+
+impl ColorModel {
+    /// Every name [`FromStr`] accepts, with the model it stands for.
+    pub const NAMES: &'static [(&'static str, ColorModel)] = &[
+        ("hsl", ColorModel::Hsl),
+        ("raw", ColorModel::Raw),
+        ("rgb", ColorModel::Raw),
+    ];
+
+    /// The name of the model as accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorModel::Raw => "raw",
+            ColorModel::Hsl => "hsl",
+        }
+    }
+}
+
+impl FromStr for ColorModel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Unlike the other names, models are case-sensitive.
+        ColorModel::NAMES.iter()
+            .find(|&&(name, _)| name == s)
+            .map(|&(_, model)| model)
+            .ok_or_else(|| Error::InvalidColorModel(s.to_owned()))
+    }
+}
+
+/// An inclusive range of fractions in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Bounds {
+    pub fn new(min: f32, max: f32) -> Result<Bounds, Error> {
+        if (0.0..=1.0).contains(&min) && (0.0..=1.0).contains(&max) && min <= max {
+            Ok(Bounds { min, max })
+        } else {
+            Err(Error::InvalidBounds(format!("{}-{}", min * 100.0, max * 100.0)))
+        }
+    }
+
+    /// Maps `byte` linearly onto the range.
+    pub fn pick(self, byte: u8) -> f32 {
+        self.min + (self.max - self.min) * f32::from(byte) / 255.0
+    }
+}
+
+impl FromStr for Bounds {
+    type Err = Error;
+
+    /// Parses a percentage such as `50`, or a range such as `40-70`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percent = |p: &str| p.trim().parse::<f32>().map(|p| p / 100.0);
+        let parsed = match s.split_once('-') {
+            Some((min, max)) => percent(min).and_then(|min| Ok((min, percent(max)?))),
+            None => percent(s).map(|p| (p, p)),
+        };
+        match parsed {
+            Ok((min, max)) => Bounds::new(min, max).map_err(|_| Error::InvalidBounds(s.to_owned())),
+            Err(_) => Err(Error::InvalidBounds(s.to_owned())),
+        }
+    }
+}
+
+/// Settings for turning digest bytes into fill colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorOptions {
+    pub model: ColorModel,
+    /// Saturation range of the [`ColorModel::Hsl`] model.
+    pub saturation: Bounds,
+    /// Lightness range of the [`ColorModel::Hsl`] model.
+    pub lightness: Bounds,
+}
+
+impl ColorOptions {
+    /// Builds an opaque colour from the first [`COLOR_BYTES`] of `bytes`.
+    ///
+    /// With [`ColorModel::Hsl`] the first two bytes pick the hue, and the
+    /// low and high nibbles of the third byte pick the saturation and the
+    /// lightness within their bounds. Returns `None` if `bytes` is too short.
+    pub fn color(&self, bytes: &[u8]) -> Option<Rgba<u8>> {
+        match (self.model, bytes) {
+            (ColorModel::Raw, _) => fill_color(bytes),
+            (ColorModel::Hsl, [h1, h2, sl, ..]) => {
+                let hue = f32::from(u16::from_be_bytes([*h1, *h2])) / 65536.0 * 360.0;
+                let saturation = self.saturation.pick((sl & 0x0f) * 17);
+                let lightness = self.lightness.pick((sl >> 4) * 17);
+                Some(hsl_to_rgb(hue, saturation, lightness))
+            }
+            (ColorModel::Hsl, _) => None,
+        }
+    }
+}
+
+impl Default for ColorOptions {
+    fn default() -> Self {
+        ColorOptions {
+            model: ColorModel::default(),
+            saturation: Bounds { min: 0.45, max: 0.75 },
+            lightness: Bounds { min: 0.35, max: 0.6 },
+        }
+    }
+}
+
+/// Number of leading digest bytes consumed by [`palette`] for `colors`
+/// entries, counting the background as one of them.
+pub fn palette_bytes(colors: u8) -> usize {
+    COLOR_BYTES * usize::from(colors.max(2) - 1)
+}
+
+/// Builds the fill colours of a palette of `colors` entries, counting the
+/// background as one of them, from consecutive groups of digest bytes.
+///
+/// The first entry always comes from the leading bytes, so a two colour
+/// palette matches single colour identicons. Returns `None` if the digest
+/// is too short.
+pub fn palette(digest: &[u8], colors: u8, options: &ColorOptions) -> Option<Vec<Rgba<u8>>> {
+    digest
+        .get(..palette_bytes(colors))?
+        .chunks(COLOR_BYTES)
+        .map(|bytes| options.color(bytes))
+        .collect()
+}
+
+const TRANSPARENT: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+/// Colour of the canvas behind the cells, including the padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Background {
+    /// A fixed colour, possibly with an alpha channel.
+    Color(Rgba<u8>),
+    /// A pale tint complementary to the fill colour.
+    Auto,
+}
+
+impl Background {
+    /// Resolves the background for an identicon filled with `fill_color`.
+    pub fn resolve(self, fill_color: Rgba<u8>) -> Rgba<u8> {
+        match self {
+            Background::Color(color) => color,
+            Background::Auto => complementary_tint(fill_color),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(TRANSPARENT)
+    }
+}
+
+impl FromStr for Background {
+    type Err = Error;
+
+    /// Parses `auto`, a named colour or a hex colour.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            Ok(Background::Auto)
+        } else {
+            parse_color(s).map(Background::Color)
+        }
+    }
+}
+
+/// Parses a named colour or a hex colour of the form `RRGGBB` or
+/// `RRGGBBAA`, with or without a leading `#`.
+pub fn parse_color(s: &str) -> Result<Rgba<u8>, Error> {
+    if let Some(color) = named_color(s) {
+        return Ok(color);
+    }
+
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(Error::InvalidColor(s.to_owned()));
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    let alpha = if hex.len() == 8 { channel(6) } else { 255 };
+    Ok(Rgba([channel(0), channel(2), channel(4), alpha]))
+}
+
+fn named_color(name: &str) -> Option<Rgba<u8>> {
+    let rgb = match name.to_ascii_lowercase().as_str() {
+        "transparent" => return Some(TRANSPARENT),
+        "black" => [0x00, 0x00, 0x00],
+        "silver" => [0xc0, 0xc0, 0xc0],
+        "gray" | "grey" => [0x80, 0x80, 0x80],
+        "white" => [0xff, 0xff, 0xff],
+        "maroon" => [0x80, 0x00, 0x00],
+        "red" => [0xff, 0x00, 0x00],
+        "purple" => [0x80, 0x00, 0x80],
+        "fuchsia" | "magenta" => [0xff, 0x00, 0xff],
+        "green" => [0x00, 0x80, 0x00],
+        "lime" => [0x00, 0xff, 0x00],
+        "olive" => [0x80, 0x80, 0x00],
+        "yellow" => [0xff, 0xff, 0x00],
+        "navy" => [0x00, 0x00, 0x80],
+        "blue" => [0x00, 0x00, 0xff],
+        "teal" => [0x00, 0x80, 0x80],
+        "aqua" | "cyan" => [0x00, 0xff, 0xff],
+        "orange" => [0xff, 0xa5, 0x00],
+        "pink" => [0xff, 0xc0, 0xcb],
+        "brown" => [0xa5, 0x2a, 0x2a],
+        "gold" => [0xff, 0xd7, 0x00],
+        "indigo" => [0x4b, 0x00, 0x82],
+        "violet" => [0xee, 0x82, 0xee],
+        "beige" => [0xf5, 0xf5, 0xdc],
+        "ivory" => [0xff, 0xff, 0xf0],
+        "lavender" => [0xe6, 0xe6, 0xfa],
+        "whitesmoke" => [0xf5, 0xf5, 0xf5],
+        "gainsboro" => [0xdc, 0xdc, 0xdc],
+        "lightgray" | "lightgrey" => [0xd3, 0xd3, 0xd3],
+        "darkgray" | "darkgrey" => [0xa9, 0xa9, 0xa9],
+        "dimgray" | "dimgrey" => [0x69, 0x69, 0x69],
+        _ => return None,
+    };
+    Some(Rgba([rgb[0], rgb[1], rgb[2], 255]))
+}
+
+/// Converts an RGB colour to hue (degrees), saturation and lightness
+/// (both in `0.0..=1.0`).
+pub fn rgb_to_hsl(color: Rgba<u8>) -> (f32, f32, f32) {
+    let [r, g, b, _] = color.0;
+    let (r, g, b) = (f32::from(r) / 255.0, f32::from(g) / 255.0, f32::from(b) / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return (0.0, 0.0, lightness);
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    (hue, saturation, lightness)
+}
+
+/// Converts hue (degrees), saturation and lightness to an opaque colour.
+pub fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> Rgba<u8> {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([channel(r), channel(g), channel(b), 255])
+}
+
+/// A light, desaturated colour on the opposite side of the colour wheel.
+pub fn complementary_tint(color: Rgba<u8>) -> Rgba<u8> {
+    let (hue, saturation, _) = rgb_to_hsl(color);
+    hsl_to_rgb(hue + 180.0, saturation.min(0.5), 0.92)
+}
+
+/// Composites `color` over the opaque `matte`.
+pub fn blend(color: Rgba<u8>, matte: Rgba<u8>) -> Rgba<u8> {
+    let alpha = u32::from(color[3]);
+    let mix = |c: u8, m: u8| ((u32::from(c) * alpha + u32::from(m) * (255 - alpha) + 127) / 255) as u8;
+    Rgba([mix(color[0], matte[0]), mix(color[1], matte[1]), mix(color[2], matte[2]), 255])
+}
+
+/// Composites `color` over `base`, with the opacity of `color` scaled by
+/// `coverage` in range 0-1.
+pub fn over(color: Rgba<u8>, coverage: f32, base: Rgba<u8>) -> Rgba<u8> {
+    let src_alpha = f32::from(color[3]) / 255.0 * coverage;
+    let dst_alpha = f32::from(base[3]) / 255.0 * (1.0 - src_alpha);
+    let alpha = src_alpha + dst_alpha;
+    if alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let mix = |c: u8, b: u8| ((f32::from(c) * src_alpha + f32::from(b) * dst_alpha) / alpha).round() as u8;
+    Rgba([
+        mix(color[0], base[0]),
+        mix(color[1], base[1]),
+        mix(color[2], base[2]),
+        (alpha * 255.0).round() as u8,
+    ])
+}
+
+/// Minimum WCAG contrast between the fill colours and the background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Contrast {
+    /// Colours are used as derived.
+    #[default]
+    Off,
+    /// A contrast ratio of at least 4.5:1.
+    Aa,
+    /// A contrast ratio of at least 7:1.
+    ///
+    /// Cannot be met on backgrounds that are not fully opaque, see
+    /// [`ensure_contrast`].
+    Aaa,
+}
+
+impl Contrast {
+    /// Every name [`FromStr`] accepts, with the level it stands for.
+    pub const NAMES: &'static [(&'static str, Contrast)] = &[
+        ("off", Contrast::Off),
+        ("none", Contrast::Off),
+        ("aa", Contrast::Aa),
+        ("aaa", Contrast::Aaa),
+    ];
+
+    /// The name of the level as accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Contrast::Off => "off",
+            Contrast::Aa => "aa",
+            Contrast::Aaa => "aaa",
+        }
+    }
+
+    /// The minimum contrast ratio, or `None` when contrast is not enforced.
+    pub fn ratio(self) -> Option<f32> {
+        match self {
+            Contrast::Off => None,
+            Contrast::Aa => Some(4.5),
+            Contrast::Aaa => Some(7.0),
+        }
+    }
+}
+
+impl FromStr for Contrast {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::lookup(Contrast::NAMES, s).ok_or_else(|| Error::InvalidContrast(s.to_owned()))
+    }
+}
+
+/// The WCAG relative luminance of an opaque colour.
+pub fn relative_luminance(color: Rgba<u8>) -> f32 {
+    let linear = |c: u8| {
+        let c = f32::from(c) / 255.0;
+        if c <= 0.039_28 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.0722 * linear(color[2])
+}
+
+/// The WCAG contrast ratio between two opaque colours, from 1 to 21.
+pub fn contrast_ratio(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Adjusts the lightness of `color` until it reaches `contrast` against
+/// `background`, keeping its hue and saturation.
+///
+/// A background that is not fully opaque could end up on top of anything,
+/// so it is checked composited onto both white and black. When the ratio
+/// cannot be met against both, the lightness with the best worst-case
+/// contrast is used instead. The lightness closest to the original that
+/// satisfies the ratio always wins, so the result is deterministic.
+///
+/// No colour has more than about 4.58:1 against both white and black, so
+/// on a transparent background [`Contrast::Aaa`] always falls back to that
+/// compromise, while [`Contrast::Aa`] can just be met.
+pub fn ensure_contrast(color: Rgba<u8>, background: Rgba<u8>, contrast: Contrast) -> Rgba<u8> {
+    let ratio = match contrast.ratio() {
+        Some(ratio) => ratio,
+        None => return color,
+    };
+
+    let backdrops = if background[3] == 255 {
+        vec![background]
+    } else {
+        vec![
+            blend(background, Rgba([255, 255, 255, 255])),
+            blend(background, Rgba([0, 0, 0, 255])),
+        ]
+    };
+    let worst = |candidate: Rgba<u8>| {
+        backdrops
+            .iter()
+            .map(|&backdrop| contrast_ratio(candidate, backdrop))
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    if worst(color) >= ratio {
+        return color;
+    }
+
+    let (hue, saturation, lightness) = rgb_to_hsl(color);
+    let mut steps: Vec<f32> = (0..=200).map(|step| step as f32 / 200.0).collect();
+    steps.sort_by(|a, b| (a - lightness).abs().total_cmp(&(b - lightness).abs()));
+    let candidates = steps.into_iter().map(|l| hsl_to_rgb(hue, saturation, l));
+
+    let mut best = color;
+    let mut best_ratio = worst(color);
+    for candidate in candidates {
+        let candidate_ratio = worst(candidate);
+        if candidate_ratio >= ratio {
+            return candidate;
+        }
+        if candidate_ratio > best_ratio {
+            best = candidate;
+            best_ratio = candidate_ratio;
+        }
+    }
+
+    best
+}
@@ -0,0 +1,95 @@
+//! Encoding of rendered identicons.
+
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbImage};
+
+use crate::color;
+
+use crate::Error;
+
+/// Output formats supported by the generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Format {
+    #[default]
+    Png,
+    Bmp,
+    Jpeg,
+    Ico,
+    Svg,
+}
+
+impl Format {
+    /// Maps a file extension to a format, falling back to PNG.
+    pub fn from_extension(extension: &str) -> Format {
+        match extension {
+            "bmp" => Format::Bmp,
+            "jpeg" => Format::Jpeg,
+            "ico" => Format::Ico,
+            "svg" => Format::Svg,
+            _ => Format::Png,
+        }
+    }
+
+    /// The file extension of the format, as accepted by
+    /// [`Format::from_extension`].
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Bmp => "bmp",
+            Format::Jpeg => "jpeg",
+            Format::Ico => "ico",
+            Format::Svg => "svg",
+        }
+    }
+
+    /// The MIME type to serve the encoded image as.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Png => "image/png",
+            Format::Bmp => "image/bmp",
+            Format::Jpeg => "image/jpeg",
+            Format::Ico => "image/x-icon",
+            Format::Svg => "image/svg+xml",
+        }
+    }
+
+    /// Whether viewers reliably honour the alpha channel of the format.
+    pub fn supports_alpha(self) -> bool {
+        !matches!(self, Format::Bmp | Format::Jpeg)
+    }
+
+    /// Whether the format is a vector format drawn from the grid rather
+    /// than from a rendered image.
+    pub fn is_vector(self) -> bool {
+        self == Format::Svg
+    }
+
+    fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            Format::Png => Some(ImageFormat::Png),
+            Format::Bmp => Some(ImageFormat::Bmp),
+            Format::Jpeg => Some(ImageFormat::Jpeg),
+            Format::Ico => Some(ImageFormat::Ico),
+            Format::Svg => None,
+        }
+    }
+}
+
+/// Encodes `img` as the raster format `format`.
+///
+/// Vector formats cannot be produced from a rendered image and are
+/// rejected with [`Error::UnsupportedFormat`]; see [`crate::svg`] instead.
+pub fn encode(img: &DynamicImage, format: Format) -> Result<Vec<u8>, Error> {
+    let image_format = format.image_format().ok_or(Error::UnsupportedFormat(format))?;
+    let mut buffer = Vec::new();
+    img.write_to(&mut buffer, image_format)?;
+    Ok(buffer)
+}
+
+/// Composites `img` onto the opaque `matte`, dropping the alpha channel.
+pub fn flatten(img: &DynamicImage, matte: Rgba<u8>) -> DynamicImage {
+    let rgb = RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, _] = color::blend(img.get_pixel(x, y), matte).0;
+        image::Rgb([r, g, b])
+    });
+    DynamicImage::ImageRgb8(rgb)
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::Format;
+
+/// Everything that can go wrong while generating an identicon.
+#[derive(Debug)]
+pub enum Error {
+    /// The grid size is zero.
+    GridSize(u32),
+    /// The grid does not fit into the requested resolution.
+    GridLargerThanResolution { grid_size: u32, resolution: u32 },
+    /// The canvas, including the padding, exceeds the maximum of
+    /// [`MAX_RESOLUTION`](crate::MAX_RESOLUTION). `side` is the longer side
+    /// of the canvas without the padding, and `padding` the padding added
+    /// along it, saturating at `u32::MAX`.
+    ResolutionTooLarge { side: u32, padding: u32 },
+    /// The padded canvas is too large for the ICO format, with `side` and
+    /// `padding` as for [`Error::ResolutionTooLarge`].
+    IcoTooLarge { side: u32, padding: u32 },
+    /// The palette size is outside of the supported range of 2-4.
+    ColorCount(u8),
+    /// A hash algorithm name could not be parsed.
+    InvalidAlgorithm(String),
+    /// A case folding mode could not be parsed.
+    InvalidCase(String),
+    /// A colour could not be parsed.
+    InvalidColor(String),
+    /// A resampling filter name could not be parsed.
+    InvalidFilter(String),
+    /// A padding shorthand could not be parsed.
+    InvalidPadding(String),
+    /// A symmetry name could not be parsed.
+    InvalidSymmetry(String),
+    /// A cell shape name could not be parsed.
+    InvalidShape(String),
+    /// A canvas mask could not be parsed.
+    InvalidMask(String),
+    /// A gap could not be parsed.
+    InvalidGap(String),
+    /// The gaps leave no room for the cells.
+    GapTooLarge(u32),
+    /// A colour model name could not be parsed.
+    InvalidColorModel(String),
+    /// A contrast level could not be parsed.
+    InvalidContrast(String),
+    /// A percentage range could not be parsed or lies outside of 0-100.
+    InvalidBounds(String),
+    /// The digest ran out of bits before every cell was assigned.
+    ExhaustedBits,
+    /// The format cannot be encoded from a rendered image.
+    UnsupportedFormat(Format),
+    /// The encoder failed to write the image.
+    Encode(image::ImageError),
+}
+
+impl Error {
+    /// A stable, machine-readable identifier for the kind of error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::GridSize(_) => "invalid_grid_size",
+            Error::GridLargerThanResolution { .. } => "grid_larger_than_resolution",
+            Error::ResolutionTooLarge { .. } => "resolution_too_large",
+            Error::IcoTooLarge { .. } => "ico_too_large",
+            Error::ColorCount(_) => "invalid_color_count",
+            Error::InvalidAlgorithm(_) => "invalid_algorithm",
+            Error::InvalidCase(_) => "invalid_case",
+            Error::InvalidColor(_) => "invalid_color",
+            Error::InvalidFilter(_) => "invalid_filter",
+            Error::InvalidPadding(_) => "invalid_padding",
+            Error::InvalidSymmetry(_) => "invalid_symmetry",
+            Error::InvalidShape(_) => "invalid_shape",
+            Error::InvalidMask(_) => "invalid_mask",
+            Error::InvalidGap(_) => "invalid_gap",
+            Error::GapTooLarge(_) => "gap_too_large",
+            Error::InvalidColorModel(_) => "invalid_color_model",
+            Error::InvalidContrast(_) => "invalid_contrast",
+            Error::InvalidBounds(_) => "invalid_bounds",
+            Error::ExhaustedBits => "exhausted_bits",
+            Error::UnsupportedFormat(_) => "unsupported_format",
+            Error::Encode(_) => "encode_failed",
+        }
+    }
+
+    /// Whether the error is caused by the configuration rather than by a
+    /// failure while generating the identicon.
+    pub fn is_invalid_input(&self) -> bool {
+        !matches!(self, Error::ExhaustedBits | Error::UnsupportedFormat(_) | Error::Encode(_))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::GridSize(_) => write!(f, "Grid size must be at least 1"),
+            Error::GridLargerThanResolution { .. } => write!(f, "Grid size cannot be larger than resolution"),
+            Error::ResolutionTooLarge { .. } => write!(f, "Canvas size (res plus padding) cannot exceed {}", crate::MAX_RESOLUTION),
+            Error::IcoTooLarge { .. } => write!(f, "ICO size (res plus padding) must be in range 1-256"),
+            Error::ColorCount(_) => write!(f, "The number of colours must be in range 2-4"),
+            Error::InvalidAlgorithm(s) => write!(f, "Invalid hash algorithm: {}", s),
+            Error::InvalidCase(s) => write!(f, "Invalid case: {}", s),
+            Error::InvalidColor(s) => write!(f, "Invalid colour: {}", s),
+            Error::InvalidFilter(s) => write!(f, "Invalid filter: {}", s),
+            Error::InvalidPadding(s) => write!(f, "Invalid padding: {}", s),
+            Error::InvalidSymmetry(s) => write!(f, "Invalid symmetry: {}", s),
+            Error::InvalidShape(s) => write!(f, "Invalid shape: {}", s),
+            Error::InvalidMask(s) => write!(f, "Invalid mask: {}", s),
+            Error::InvalidGap(s) => write!(f, "Invalid gap: {}", s),
+            Error::GapTooLarge(gap) => write!(f, "A gap of {} pixels leaves no room for the cells", gap),
+            Error::InvalidColorModel(s) => write!(f, "Invalid colour model: {}", s),
+            Error::InvalidContrast(s) => write!(f, "Invalid contrast level: {}", s),
+            Error::InvalidBounds(s) => write!(f, "Invalid percentage range: {}", s),
+            Error::ExhaustedBits => write!(f, "The digest does not contain enough bits for the grid"),
+            Error::UnsupportedFormat(format) => write!(f, "{:?} cannot be encoded from a raster image", format),
+            Error::Encode(e) => write!(f, "Unable to encode image: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Encode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Encode(e)
+    }
+}
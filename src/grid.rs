@@ -0,0 +1,212 @@
+//! The cell matrix an identicon is drawn from.
+
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Number of digest bits each cell consumes for a palette of `colors`
+/// entries, counting the background as one of them.
+pub fn bits_per_cell(colors: u8) -> usize {
+    1 + selector_bits(colors.max(2) - 1)
+}
+
+/// Number of bits choosing between `fills` fill colours. Three fills cannot
+/// be chosen evenly from whole bits, so they take six, which favours the
+/// first fill by less than two percent.
+fn selector_bits(fills: u8) -> usize {
+    match fills {
+        1 => 0,
+        2 => 1,
+        _ => 6,
+    }
+}
+
+/// Groups `bits` into cell values for a palette of `colors` entries.
+///
+/// A value of 0 leaves the cell empty; any other value is the 1-based index
+/// of the fill colour. The first bit of every cell decides whether it is
+/// filled, so half of the cells are empty whatever the number of colours,
+/// and the bits after it pick the fill colour.
+pub fn cell_values<I>(bits: I, colors: u8) -> impl Iterator<Item = u8>
+where
+    I: IntoIterator<Item = bool>,
+{
+    let fills = colors.max(2) - 1;
+    let width = selector_bits(fills);
+    let mut bits = bits.into_iter();
+    std::iter::from_fn(move || {
+        let filled = bits.next()?;
+        let mut selector = 0u8;
+        for i in 0..width {
+            selector |= u8::from(bits.next()?) << i;
+        }
+        Some(if filled { selector % fills + 1 } else { 0 })
+    })
+}
+
+/// Which cells of the grid repeat each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Symmetry {
+    /// The right half mirrors the left half.
+    #[default]
+    Horizontal,
+    /// The bottom half mirrors the top half.
+    Vertical,
+    /// Both halves are mirrored, so every quarter repeats the top-left one.
+    Quad,
+    /// The grid looks the same after every quarter turn. Grids that are not
+    /// square only repeat after a half turn, like [`Symmetry::Rot2`].
+    Rot4,
+    /// The grid looks the same after half a turn.
+    Rot2,
+    /// Every cell is independent.
+    None,
+}
+
+impl Symmetry {
+    /// Every name [`FromStr`] accepts, with the symmetry it stands for.
+    /// Boolean spellings map to [`Symmetry::Horizontal`] and
+    /// [`Symmetry::None`].
+    pub const NAMES: &'static [(&'static str, Symmetry)] = &[
+        ("horizontal", Symmetry::Horizontal),
+        ("vertical", Symmetry::Vertical),
+        ("quad", Symmetry::Quad),
+        ("rot4", Symmetry::Rot4),
+        ("rot2", Symmetry::Rot2),
+        ("none", Symmetry::None),
+        ("", Symmetry::Horizontal),
+        ("true", Symmetry::Horizontal),
+        ("1", Symmetry::Horizontal),
+        ("yes", Symmetry::Horizontal),
+        ("on", Symmetry::Horizontal),
+        ("false", Symmetry::None),
+        ("0", Symmetry::None),
+        ("no", Symmetry::None),
+        ("off", Symmetry::None),
+    ];
+
+    /// The name of the symmetry as accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::Horizontal => "horizontal",
+            Symmetry::Vertical => "vertical",
+            Symmetry::Quad => "quad",
+            Symmetry::Rot4 => "rot4",
+            Symmetry::Rot2 => "rot2",
+            Symmetry::None => "none",
+        }
+    }
+
+    /// The cells that repeat the cell at column `x`, row `y` of a grid of
+    /// `columns` by `rows` cells, including the cell itself.
+    pub fn orbit(self, x: u32, y: u32, columns: u32, rows: u32) -> Vec<(u32, u32)> {
+        let (mx, my) = (columns - 1 - x, rows - 1 - y);
+        match self {
+            Symmetry::Horizontal => vec![(x, y), (mx, y)],
+            Symmetry::Vertical => vec![(x, y), (x, my)],
+            Symmetry::Quad => vec![(x, y), (mx, y), (x, my), (mx, my)],
+            Symmetry::Rot4 if columns == rows => vec![(x, y), (my, x), (mx, my), (y, mx)],
+            Symmetry::Rot4 | Symmetry::Rot2 => vec![(x, y), (mx, my)],
+            Symmetry::None => vec![(x, y)],
+        }
+    }
+
+    /// Number of cells of a grid of `columns` by `rows` cells that are
+    /// assigned a value of their own, one per [`orbit`](Symmetry::orbit).
+    pub fn free_cells(self, columns: u32, rows: u32) -> usize {
+        let (columns, rows) = (columns as usize, rows as usize);
+        match self {
+            Symmetry::Horizontal => columns.div_ceil(2) * rows,
+            Symmetry::Vertical => columns * rows.div_ceil(2),
+            Symmetry::Quad => columns.div_ceil(2) * rows.div_ceil(2),
+            Symmetry::Rot4 if columns == rows => (columns * rows).div_ceil(4),
+            Symmetry::Rot4 | Symmetry::Rot2 => (columns * rows).div_ceil(2),
+            Symmetry::None => columns * rows,
+        }
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = Error;
+
+    /// Parses one of the [`Symmetry::NAMES`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::lookup(Symmetry::NAMES, s).ok_or_else(|| Error::InvalidSymmetry(s.to_owned()))
+    }
+}
+
+/// A matrix of cell values, 0 where the cell is empty and the 1-based fill
+/// colour index elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    columns: u32,
+    rows: u32,
+    cells: Vec<u8>,
+}
+
+impl Grid {
+    /// Assigns one value per cell in row-major order.
+    ///
+    /// Each value is copied to the whole [`orbit`](Symmetry::orbit) of its
+    /// cell under `symmetry`, and cells that were already assigned this way
+    /// consume no value of their own.
+    pub fn from_cells<I>(columns: u32, rows: u32, symmetry: Symmetry, values: I) -> Result<Grid, Error>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let mut values = values.into_iter();
+        let mut cells = vec![0; (columns * rows) as usize];
+        let mut assigned = vec![false; cells.len()];
+
+        for y in 0..rows {
+            for x in 0..columns {
+                if assigned[(y * columns + x) as usize] {
+                    continue;
+                }
+                let value = values.next().ok_or(Error::ExhaustedBits)?;
+                for (ox, oy) in symmetry.orbit(x, y, columns, rows) {
+                    let i = (oy * columns + ox) as usize;
+                    cells[i] = value;
+                    assigned[i] = true;
+                }
+            }
+        }
+
+        Ok(Grid { columns, rows, cells })
+    }
+
+    /// Assigns one bit per cell, as a two colour grid.
+    pub fn from_bits<I>(columns: u32, rows: u32, symmetry: Symmetry, bits: I) -> Result<Grid, Error>
+    where
+        I: IntoIterator<Item = bool>,
+    {
+        Grid::from_cells(columns, rows, symmetry, cell_values(bits, 2))
+    }
+
+    /// Number of cells along the horizontal axis.
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// Number of cells along the vertical axis.
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// The value of the cell at column `x`, row `y`, or `None` outside of
+    /// the grid.
+    pub fn cell(&self, x: i64, y: i64) -> Option<u8> {
+        let columns = i64::from(self.columns);
+        if (0..columns).contains(&x) && (0..i64::from(self.rows)).contains(&y) {
+            Some(self.cells[(y * columns + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Whether the cell at column `x`, row `y` is filled. Cells outside of
+    /// the grid are never filled.
+    pub fn is_filled(&self, x: i64, y: i64) -> bool {
+        self.cell(x, y).is_some_and(|value| value != 0)
+    }
+}
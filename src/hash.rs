@@ -0,0 +1,212 @@
+//! Hashing of names and extraction of the cell bits from the digest.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crypto::blake2b::Blake2b;
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::md5::Md5;
+use crypto::sha1::Sha1;
+use crypto::sha2::{Sha224, Sha256, Sha384, Sha512};
+use crypto::sha3::Sha3;
+
+use crate::Error;
+
+/// The hash function a name is digested with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Algorithm {
+    /// The smallest SHA-2 variant covering the grid, see [`hasher_for`].
+    ///
+    /// Colours and patterns change with the grid size.
+    #[default]
+    Sized,
+    /// MD5, as used for Gravatar email hashes.
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    /// BLAKE2b with a 64 byte digest.
+    Blake2b,
+    /// BLAKE3 extendable output.
+    Blake3,
+    /// SHAKE256 extendable output.
+    ///
+    /// The leading bytes, and so the colours and the first cells, are the
+    /// same for every grid size and as many bytes as needed are produced.
+    Shake256,
+}
+
+impl Algorithm {
+    /// Every name [`FromStr`] accepts, with the algorithm it stands for.
+    pub const NAMES: &'static [(&'static str, Algorithm)] = &[
+        ("sized", Algorithm::Sized),
+        ("md5", Algorithm::Md5),
+        ("sha1", Algorithm::Sha1),
+        ("sha256", Algorithm::Sha256),
+        ("sha512", Algorithm::Sha512),
+        ("blake2b", Algorithm::Blake2b),
+        ("blake3", Algorithm::Blake3),
+        ("shake256", Algorithm::Shake256),
+    ];
+
+    /// The name of the algorithm as accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sized => "sized",
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Blake2b => "blake2b",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Shake256 => "shake256",
+        }
+    }
+
+    /// The name of the hash function names are digested with for a grid of
+    /// `grid_size`, which for [`Algorithm::Sized`] is the SHA-2 variant
+    /// [`hasher_for`] picks.
+    pub fn name_for(self, grid_size: u32) -> &'static str {
+        match self {
+            Algorithm::Sized => match hasher_for(grid_size).output_bits() {
+                224 => "sha224",
+                256 => "sha256",
+                384 => "sha384",
+                _ => "sha512",
+            },
+            _ => self.name(),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::lookup(Algorithm::NAMES, s).ok_or_else(|| Error::InvalidAlgorithm(s.to_owned()))
+    }
+}
+
+/// A versioned secret for keyed hashing.
+///
+/// The secret is never printed, not even by the [`Debug`] implementation.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key {
+    version: String,
+    secret: Vec<u8>,
+}
+
+impl Key {
+    pub fn new<V: Into<String>, S: Into<Vec<u8>>>(version: V, secret: S) -> Key {
+        Key {
+            version: version.into(),
+            secret: secret.into(),
+        }
+    }
+
+    /// The version identifying this key during rotation.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// A short digest identifying the secret, so that output derived from a
+    /// replaced secret can be told apart without revealing either.
+    pub fn fingerprint(&self) -> String {
+        keyed("identicon key fingerprint", self)[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("version", &self.version)
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Replaces `name` with its HMAC-SHA256 under `key`.
+///
+/// The result is digested in place of the name, so identicons cannot be
+/// precomputed for a list of names without knowing the secret.
+pub fn keyed(name: &str, key: &Key) -> Vec<u8> {
+    let mut hmac = Hmac::new(Sha256::new(), &key.secret);
+    hmac.input(name.as_bytes());
+    hmac.result().code().to_vec()
+}
+
+/// Returns the smallest hasher whose output covers a grid of `grid_size`,
+/// or SHA-512 for grids larger than any SHA-2 output covers.
+///
+/// Max of match is floor(sqrt(output_size - 32))
+/// because real size of needed minimum output is
+/// grid_size * 2 and 32 bits are reserved for color
+pub fn hasher_for(grid_size: u32) -> Box<dyn Digest> {
+    match grid_size {
+        0..=13 => Box::new(Sha224::new()),
+        14 => Box::new(Sha256::new()),
+        15..=18 => Box::new(Sha384::new()),
+        _ => Box::new(Sha512::new()),
+    }
+}
+
+/// Hashes `input` into at least `len` bytes.
+///
+/// Extendable output functions are squeezed for exactly as many bytes as
+/// needed. Fixed size digests are extended by repeatedly appending the hash
+/// of the previous block, so their leading bytes never depend on `len`.
+pub fn digest(input: &[u8], algorithm: Algorithm, grid_size: u32, len: usize) -> Vec<u8> {
+    match algorithm {
+        Algorithm::Sized => extend(|| hasher_for(grid_size), input, len),
+        Algorithm::Md5 => extend(|| Box::new(Md5::new()), input, len),
+        Algorithm::Sha1 => extend(|| Box::new(Sha1::new()), input, len),
+        Algorithm::Sha256 => extend(|| Box::new(Sha256::new()), input, len),
+        Algorithm::Sha512 => extend(|| Box::new(Sha512::new()), input, len),
+        Algorithm::Blake2b => extend(|| Box::new(Blake2b::new(64)), input, len),
+        Algorithm::Blake3 => {
+            let mut bytes = vec![0; len];
+            blake3::Hasher::new()
+                .update(input)
+                .finalize_xof()
+                .fill(&mut bytes);
+            bytes
+        }
+        Algorithm::Shake256 => {
+            let mut hasher = Sha3::shake_256();
+            let mut bytes = vec![0; len];
+            hasher.input(input);
+            hasher.result(&mut bytes);
+            bytes
+        }
+    }
+}
+
+fn extend<F>(new_hasher: F, input: &[u8], len: usize) -> Vec<u8>
+where
+    F: Fn() -> Box<dyn Digest>,
+{
+    let mut hasher = new_hasher();
+    let block_len = hasher.output_bytes();
+    let mut bytes = vec![0; block_len];
+    hasher.input(input);
+    hasher.result(&mut bytes);
+
+    while bytes.len() < len {
+        let mut hasher = new_hasher();
+        let mut block = vec![0; block_len];
+        hasher.input(&bytes[bytes.len() - block_len..]);
+        hasher.result(&mut block);
+        bytes.extend_from_slice(&block);
+    }
+
+    bytes
+}
+
+/// Iterates over the bits of `bytes`, least significant bit first.
+pub fn bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+}
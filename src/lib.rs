@@ -0,0 +1,44 @@
+//! Deterministic identicons generated from the hash of a name.
+//!
+//! The [`IdenticonBuilder`] covers the common case. The individual steps
+//! of the pipeline (name canonicalisation in [`normalize`], hashing in
+//! [`hash`], colour derivation in [`color`],
+//! cell assignment in [`grid`], painting in [`render`] with the cell
+//! outlines of [`shape`] and encoding in
+//! [`encode`] or [`svg`]) are also exposed for callers that need to
+//! customise one of them.
+
+mod builder;
+pub mod color;
+pub mod encode;
+mod error;
+pub mod grid;
+pub mod hash;
+pub mod normalize;
+pub mod render;
+pub mod shape;
+pub mod svg;
+
+pub use builder::{Identicon, IdenticonBuilder};
+pub use encode::Format;
+pub use error::Error;
+
+/// Largest canvas side, including the padding, in pixels, that will be
+/// rendered.
+pub const MAX_RESOLUTION: u32 = 1000;
+
+/// Version of the output for a given name and configuration. Bump it
+/// whenever a change alters the bytes produced for existing parameters, so
+/// caches of earlier output are invalidated.
+pub const RENDER_VERSION: u32 = 2;
+
+/// The value `s` names in a table of `(name, value)` pairs, ignoring ASCII
+/// case.
+pub(crate) fn lookup<T: Copy>(names: &[(&str, T)], s: &str) -> Option<T> {
+    names.iter().find(|(name, _)| name.eq_ignore_ascii_case(s)).map(|&(_, value)| value)
+}
+
+/// Rounds `n` to the nearest multiple of `m`.
+pub fn closest_multiple(n: u32, m: u32) -> u32 {
+    (m as f32 * (n as f32 / m as f32).round()) as u32
+}
@@ -1,184 +1,258 @@
 use std::convert::Infallible;
 use std::net::SocketAddr;
-use hyper::{Method, Body, Request, Response, Server};
+use hyper::body::Bytes;
+use hyper::{Method, Body, Request, Response, Server, StatusCode};
 use hyper::service::{make_service_fn, service_fn};
-use std::collections::HashMap;
-use std::path::Path;
-use std::ffi::OsStr;
-use std::str::FromStr;
-use crypto::digest::Digest;
-use crypto::sha2::{Sha224, Sha256, Sha384, Sha512};
-use image::{Rgba, GenericImage, DynamicImage, ImageFormat};
-
-type QueryParams<'a> = HashMap<&'a str, &'a str>;
-
-fn parse_query_param_or<'a, T: FromStr + Copy>(query: &QueryParams<'a>, key: &'a str, default: T) -> T {
-    query.get(key)
-        .map(|s| s.parse::<T>().unwrap_or(default))
-        .unwrap_or(default)
+use identicon_generator::{Error, Format, Identicon, IdenticonBuilder, MAX_RESOLUTION, RENDER_VERSION};
+use identicon_generator::color::{Background, Contrast};
+use identicon_generator::grid::Symmetry;
+use identicon_generator::hash::{Algorithm, Key};
+use identicon_generator::normalize::Case;
+use identicon_generator::render::{Filter, Gap, Padding};
+use identicon_generator::shape::{Mask, Shape};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod server;
+
+use server::State;
+use server::cache::{self, Stats};
+use server::config::Config;
+use server::error::ServerError;
+use server::query::{accepts_json, matches_etag, parse_file_name, Flag, Params, Problem};
+
+/// An identicon to generate, as requested.
+struct Job {
+    identicon: Identicon,
+    name: String,
+    key: Option<Key>,
+    /// Malformed parameters that were replaced by their defaults.
+    ignored: Vec<String>,
 }
 
-fn fill_square(img: &mut DynamicImage, x: u32, y: u32, s: u32, c: Rgba<u8>) {
-    for py in y..(y+s) {
-        for px in x..(x+s) {
-            img.put_pixel(px, py, c);
+async fn gen_identicon(state: Arc<State>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let json = accepts_json(&req);
+    Ok(handle(&state, req).await.unwrap_or_else(|e| e.into_response(json)))
+}
+
+async fn handle(state: &Arc<State>, req: Request<Body>) -> Result<Response<Body>, ServerError> {
+    let Job { identicon, name, key, ignored } = prepare(&state.config, &req)?;
+    let format = identicon.format();
+    let cache_key = identicon.cache_key(&name);
+    let etag = format!("\"v{}-{}\"", RENDER_VERSION, &cache::digest(&cache_key)[..32]);
+
+    let mut response = Response::builder()
+        .header("ETag", &etag)
+        .header("Cache-Control", format!("public, max-age={}, immutable", state.config.max_age))
+        .header("X-Hash-Algorithm", identicon.hash_algorithm());
+    if let Some(key) = &key {
+        response = response.header("X-Key-Version", key.version());
+    }
+    if !ignored.is_empty() {
+        response = response.header("X-Ignored-Parameters", ignored.join(", "));
+    }
+    if matches_etag(&req, &etag) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED).body(Body::empty())?);
+    }
+
+    let cached = cached(state, &cache_key).await;
+    let hit = cached.is_some();
+    let encoded = match cached {
+        Some(encoded) => encoded,
+        None => {
+            let encoded = Bytes::from(state.pool.run(move || identicon.encode(&name)).await??);
+            state.cache.insert(cache_key.clone(), encoded.clone());
+            write_disk(state, cache_key, encoded.clone());
+            encoded
         }
+    };
+
+    response = response.header("Content-Type", format.mime_type());
+    if state.cache.is_enabled() || state.disk.is_some() {
+        response = response.header("X-Cache", if hit { "HIT" } else { "MISS" });
     }
+
+    Ok(response.body(Body::from(encoded))?)
 }
 
-fn closest_multiple(n: u32, m: u32) -> u32 {
-    (m as f32 * (n as f32 / m as f32).round()) as u32
+/// The encoded identicon for `key` from memory, or from disk, in which case
+/// it is kept in memory from then on.
+async fn cached(state: &Arc<State>, key: &str) -> Option<Bytes> {
+    if let Some(encoded) = state.cache.get(key) {
+        return Some(encoded);
+    }
+    let encoded = Bytes::from(read_disk(state, key.to_owned()).await?);
+    state.cache.insert(key.to_owned(), encoded.clone());
+    Some(encoded)
 }
 
-async fn gen_identicon(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    if req.method() != &Method::GET {
-        return Ok(
-            Response::builder()
-                .status(405)
-                .body("Only GET requests are supported".into())
-                .unwrap()
-        );
-    }
-
-    let query: QueryParams = req.uri().query()
-        .map(|q|
-            q.split('&').filter_map(|p| {
-                let mut kv = p.split('=');
-                kv.next().and_then(|k| kv.next().and_then(|v| Some((k, v))))
-            })
-            .collect()
-        )
-        .unwrap_or(HashMap::new());
-
-    let path = Path::new(req.uri().path());
-    let grid_size = parse_query_param_or(&query, "size", 5);
-    let padding = parse_query_param_or(&query, "pad", 0);
-    let resolution = parse_query_param_or(&query, "res", closest_multiple(200, grid_size));
-    let symmetrical = parse_query_param_or(&query, "sym", true);
-    let file_name = path.file_stem().and_then(OsStr::to_str);
-    let extension = path.extension().and_then(OsStr::to_str).unwrap_or("png");
-    let cell_size = resolution / grid_size;
-    let size = resolution + padding * 2;
-
-    if file_name.is_none() {
-        return Ok(Response::builder()
-                .status(404)
-                .body("No name was provided".into())
-                .unwrap()
-        );
-    }
-
-    if resolution % grid_size != 0 {
-        let rounded = closest_multiple(resolution, grid_size);
-        return Ok(Response::builder()
-            .status(400)
-            .header("X-Recommended-Size", rounded)
-            .body("The resolution must be evenly divisible by the size".into())
-            .unwrap()
-        );
-    }
-
-    if resolution > 1000 {
-        return Ok(Response::builder()
-            .status(400)
-            .body("Resolution cannot exceed 1000".into())
-            .unwrap()
-        );
-    }
-
-    if size > 256 && extension == "ico" {
-        return Ok(Response::builder()
-            .status(400)
-            .body("ICO size (pad * 2 + res) must be in range 1-256".into())
-            .unwrap()
-        );
-    }
-
-    if cell_size == 0 {
-        return Ok(
-            Response::builder()
-                .status(400)
-                .body("Grid size cannot be larger than resolution".into())
-                .unwrap()
-        );
-    }
-
-    // Max of match is floor(sqrt(output_size - 32))
-    // because real size of needed minimum output is
-    // grid_size * 2 and 32 bits are reserved for color
-    let mut hasher: Box<dyn Digest> = match grid_size {
-        1..=13 => Box::new(Sha224::new()),
-        14 => Box::new(Sha256::new()),
-        15..=18 => Box::new(Sha384::new()),
-        19..=21 => Box::new(Sha512::new()),
-        _ => {
-            return Ok(
-                Response::builder()
-                    .status(400)
-                    .body("Grid size must be less from range 1-21".into())
-                    .unwrap()
-            );
-        }
-    };
+/// Reads `key` from the disk cache, if there is one and it is not
+/// saturated.
+async fn read_disk(state: &Arc<State>, key: String) -> Option<Vec<u8>> {
+    state.disk.as_ref()?;
+    let reader = state.clone();
+    let read = move || reader.disk.as_ref().and_then(|disk| disk.get(&key));
+    state.disk_io.run(read).await.ok().flatten()
+}
+
+/// Writes `encoded` for `key` to the disk cache in the background, if there
+/// is one and it is not saturated.
+fn write_disk(state: &Arc<State>, key: String, encoded: Bytes) {
+    if state.disk.is_none() {
+        return;
+    }
+    let state = state.clone();
+    tokio::spawn(async move {
+        let writer = state.clone();
+        let write = move || {
+            if let Some(disk) = &writer.disk {
+                disk.insert(&key, &encoded);
+            }
+        };
+        // A saturated disk only costs the cache an entry.
+        let _ = state.disk_io.run(write).await;
+    });
+}
+
+fn prepare(config: &Config, req: &Request<Body>) -> Result<Job, ServerError> {
+    if req.method() != Method::GET {
+        return Err(ServerError::MethodNotAllowed);
+    }
+
+    let mut params = req.uri().query().map(Params::parse).unwrap_or_default();
+    let strict = params.get_or("strict", Flag(config.strict)).0 || config.strict;
+
+    let (file_name, extension) = parse_file_name(req.uri().path()).ok_or(ServerError::MissingName)?;
+    let format = Format::from_extension(extension.as_deref().unwrap_or("png"));
 
-    let mut bytes = Vec::with_capacity(hasher.output_bytes());
-    bytes.resize(hasher.output_bytes(), 0);
-    hasher.input_str(file_name.unwrap());
-    hasher.result(&mut bytes);
-
-    let mut bytes_iter = bytes.into_iter();
-    let r = bytes_iter.next().unwrap();
-    let g = bytes_iter.next().unwrap();
-    let b = bytes_iter.next().unwrap();
-    let fill_color = Rgba([r, g, b, 255]);
-
-    let mut bits = bytes_iter
-        .map(|byte| {
-            vec![
-                ((byte >> 0) & 1u8) == 1u8,
-                ((byte >> 1) & 1u8) == 1u8,
-                ((byte >> 2) & 1u8) == 1u8,
-                ((byte >> 3) & 1u8) == 1u8,
-                ((byte >> 4) & 1u8) == 1u8,
-                ((byte >> 5) & 1u8) == 1u8,
-                ((byte >> 6) & 1u8) == 1u8,
-                ((byte >> 7) & 1u8) == 1u8,
-            ]
-        })
-        .flatten();
-
-    let mut formatted_buffer = Vec::new();
-    let mut img = DynamicImage::new_rgba8(size, size);
-    let stop = if symmetrical {
-        (resolution as f32 - cell_size as f32 * grid_size as f32 * 0.5f32) as u32
-    } else {
-        resolution
+    let key = config.key(params.get::<String>("keyver").as_deref())?.cloned();
+    let padding = params.get::<Padding>("pad");
+    let mut builder = IdenticonBuilder::new()
+        .grid_size(params.get_or("size", 5))
+        .padding(padding.unwrap_or_default())
+        .symmetry(params.get_or("sym", Symmetry::default()))
+        .shape(params.get_or("shape", Shape::default()))
+        .mask(params.get_or("mask", Mask::default()))
+        .gap(params.get_or("gap", Gap::default()))
+        .filter(params.get_or("filter", Filter::default()))
+        .case(params.get_or("case", Case::default()))
+        .trim(params.get_or("trim", Flag(false)).0)
+        .algorithm(params.get_or("algo", Algorithm::default()))
+        .colors(params.get_or("colors", 2))
+        .color_model(params.get_or("color_model", config.color_options.model))
+        .saturation(params.get_or("sat", config.color_options.saturation))
+        .lightness(params.get_or("light", config.color_options.lightness))
+        .background(params.get_or("bg", Background::default()))
+        .contrast(params.get_or("contrast", Contrast::default()))
+        .format(format);
+    if let Some(key) = &key {
+        builder = builder.key(key.clone());
+    }
+    let sizes = Sizes {
+        columns: params.get("cols"),
+        rows: params.get("rows"),
+        resolution: params.get("res"),
+        width: params.get("w"),
+        height: params.get("h"),
+        padded: padding.is_some(),
     };
+    if let Some(columns) = sizes.columns {
+        builder = builder.columns(columns);
+    }
+    if let Some(rows) = sizes.rows {
+        builder = builder.rows(rows);
+    }
+    if let Some(resolution) = sizes.resolution {
+        builder = builder.resolution(resolution);
+    }
+    if let Some(width) = sizes.width {
+        builder = builder.width(width);
+    }
+    if let Some(height) = sizes.height {
+        builder = builder.height(height);
+    }
+
+    let problems = params.problems();
+    if strict && !problems.is_empty() {
+        return Err(ServerError::InvalidQuery(problems));
+    }
+
+    let identicon = builder.build().map_err(|error| match sizes.blame(&error) {
+        Some(parameter) => ServerError::InvalidParameter { parameter, error },
+        None => ServerError::Identicon(error),
+    })?;
+    let ignored = problems.iter()
+        .filter(|problem| matches!(problem, Problem::Invalid { .. }))
+        .map(|problem| problem.parameter().to_owned())
+        .collect();
+
+    Ok(Job {
+        identicon,
+        name: file_name,
+        key,
+        ignored,
+    })
+}
+
+/// The size parameters of a request, to tell which of them made building
+/// the identicon fail.
+struct Sizes {
+    columns: Option<u32>,
+    rows: Option<u32>,
+    resolution: Option<u32>,
+    width: Option<u32>,
+    height: Option<u32>,
+    padded: bool,
+}
 
-    for cy in (padding..resolution).step_by(cell_size as usize) {
-        for cx in (padding..stop).step_by(cell_size as usize) {
-            if bits.next().unwrap() {
-                fill_square(&mut img, cx, cy, cell_size, fill_color);
-                if symmetrical {
-                    fill_square(&mut img, size - cx - cell_size, cy, cell_size, fill_color);
-                }
+impl Sizes {
+    /// The parameter at fault for `error`, where the error alone does not
+    /// tell.
+    fn blame(&self, error: &Error) -> Option<&'static str> {
+        let grid = |grid_size: u32| {
+            if self.columns == Some(grid_size) {
+                "cols"
+            } else if self.rows == Some(grid_size) {
+                "rows"
+            } else {
+                "size"
             }
+        };
+        let sides = [(self.width, "w"), (self.height, "h"), (self.resolution, "res")];
+        let side = |limit: u32| match sides.iter().find(|(side, _)| side.is_some_and(|side| side > limit)) {
+            Some(&(_, name)) => name,
+            None if self.padded => "pad",
+            None => sides.iter().find(|(side, _)| side.is_some()).map_or("size", |&(_, name)| name),
+        };
+        match *error {
+            Error::GridSize(_) => Some(grid(0)),
+            Error::GridLargerThanResolution { grid_size, .. } => Some(grid(grid_size)),
+            Error::ResolutionTooLarge { .. } => Some(side(MAX_RESOLUTION)),
+            Error::IcoTooLarge { .. } => Some(side(256)),
+            _ => None,
         }
     }
+}
 
-    img.write_to(&mut formatted_buffer, match extension {
-        "bmp" => ImageFormat::Bmp,
-        "jpeg" => ImageFormat::Jpeg,
-        "ico" => ImageFormat::Ico,
-        _ => ImageFormat::Png,
-    }).expect("Unable to write to formatted buffer");
+async fn log_cache_stats(state: Arc<State>) {
+    let period = Duration::from_secs(state.config.cache_stats_interval);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        interval.tick().await;
+        if state.cache.is_enabled() {
+            log_stats("cache", state.cache.stats());
+        }
+        if let Some(disk) = &state.disk {
+            log_stats("disk cache", disk.stats());
+        }
+    }
+}
 
-    Ok(
-        Response::builder()
-            .header("Content-Type", format!("image/{}", extension))
-            .body(Body::from(formatted_buffer))
-            .unwrap()
-    )
+fn log_stats(name: &str, stats: Stats) {
+    eprintln!(
+        "{}: {} hits, {} misses, {} entries, {} bytes",
+        name, stats.hits, stats.misses, stats.entries, stats.size
+    );
 }
 
 #[tokio::main]
@@ -190,8 +264,16 @@ async fn main() {
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
 
-    let make_svc = make_service_fn(|_conn| async {
-        Ok::<_, Infallible>(service_fn(gen_identicon))
+    let state = Arc::new(State::new(Config::from_env()));
+    if (state.cache.is_enabled() || state.disk.is_some()) && state.config.cache_stats_interval > 0 {
+        tokio::spawn(log_cache_stats(state.clone()));
+    }
+
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| gen_identicon(state.clone(), req)))
+        }
     });
 
     let server = Server::bind(&addr).serve(make_svc);
@@ -200,3 +282,229 @@ async fn main() {
         eprintln!("server error: {}", e);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A state independent of the environment the tests run in.
+    fn state() -> Arc<State> {
+        Arc::new(State::new(Config::default()))
+    }
+
+    async fn get(uri: &str) -> Response<Body> {
+        let state = state();
+        let req = Request::get(uri).body(Body::empty()).unwrap();
+        gen_identicon(state, req).await.unwrap()
+    }
+
+    async fn get_error(uri: &str) -> serde_json::Value {
+        let state = state();
+        let req = Request::get(uri).header("Accept", "application/json").body(Body::empty()).unwrap();
+        let response = gen_identicon(state, req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap()["error"].take()
+    }
+
+    async fn body_of(state: &Arc<State>, uri: &str) -> (Option<String>, Bytes) {
+        let req = Request::get(uri).body(Body::empty()).unwrap();
+        let response = gen_identicon(state.clone(), req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        let version = response.headers().get("X-Key-Version").map(|v| v.to_str().unwrap().to_owned());
+        (version, hyper::body::to_bytes(response.into_body()).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn rotating_keys_changes_the_identicon() {
+        let keys = || vec![Key::new("v1", "first"), Key::new("v2", "second")];
+        let state = Arc::new(State::new(Config::default().with_keys(keys(), None)));
+        let (_, unkeyed) = body_of(&self::state(), "/xoltia.svg").await;
+        let (version, current) = body_of(&state, "/xoltia.svg").await;
+        let (_, v1) = body_of(&state, "/xoltia.svg?keyver=v1").await;
+        let (_, v2) = body_of(&state, "/xoltia.svg?keyver=v2").await;
+        assert_eq!(version.as_deref(), Some("v2"));
+        assert_eq!(current, v2);
+        assert_ne!(v1, v2);
+        assert_ne!(unkeyed, v1);
+        assert_ne!(unkeyed, v2);
+
+        // HMAC_KEY_VERSION picks the key used when none is asked for.
+        let state = Arc::new(State::new(Config::default().with_keys(keys(), Some("v1"))));
+        assert_eq!(body_of(&state, "/xoltia.svg").await, (Some("v1".to_owned()), v1));
+    }
+
+    fn error_code(response: &Response<Body>) -> Option<&str> {
+        response.headers().get("X-Error-Code").and_then(|code| code.to_str().ok())
+    }
+
+    #[tokio::test]
+    async fn oversized_padding_is_a_client_error() {
+        for pad in &["4294967295", "4294967295 0", "20000", "0 0 0 801"] {
+            let response = get(&format!("/xoltia.png?res=200&pad={}", pad.replace(' ', "+"))).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "pad={}", pad);
+            assert_eq!(error_code(&response), Some("resolution_too_large"), "pad={}", pad);
+        }
+    }
+
+    #[tokio::test]
+    async fn extreme_values_are_client_errors() {
+        let parameters = ["size", "cols", "rows", "res", "w", "h", "pad", "gap", "colors", "mask=rounded:"];
+        let values = ["0", "1", "4294967295", "4294967296", "-1", "1e9", "100%", "0.99"];
+        let mut queries: Vec<String> = parameters.iter()
+            .flat_map(|parameter| {
+                let separator = if parameter.ends_with(':') { "" } else { "=" };
+                values.iter().map(move |value| format!("{}{}{}", parameter, separator, value))
+            })
+            .collect();
+        queries.extend([
+            "size=1000&res=1000&gap=4294967295",
+            "cols=4294967295&w=1000",
+            "rows=4294967295&h=1000&w=1",
+            "w=1000&h=1&pad=100%",
+            "size=1&gap=0.99&mask=rounded:4294967295",
+        ].iter().map(|query| query.to_string()));
+
+        for query in &queries {
+            for extension in &["png", "svg", "ico"] {
+                let uri = format!("/xoltia.{}?{}", extension, query);
+                let status = get(&uri).await.status();
+                assert!(status == StatusCode::OK || status == StatusCode::BAD_REQUEST, "{} gave {}", uri, status);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_padding_is_rejected_in_strict_mode() {
+        for pad in &["-1", "ten", "1+2+3", "99999999999"] {
+            let response = get(&format!("/xoltia.png?pad={}", pad)).await;
+            assert_eq!(response.status(), StatusCode::OK, "pad={}", pad);
+
+            let response = get(&format!("/xoltia.png?strict=1&pad={}", pad)).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "pad={}", pad);
+            assert_eq!(error_code(&response), Some("invalid_query"), "pad={}", pad);
+        }
+    }
+
+    async fn get_if_none_match(uri: &str, tags: &str) -> Response<Body> {
+        let req = Request::get(uri).header("If-None-Match", tags).body(Body::empty()).unwrap();
+        gen_identicon(state(), req).await.unwrap()
+    }
+
+    fn etag(response: &Response<Body>) -> String {
+        response.headers()["ETag"].to_str().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    async fn matching_etags_are_not_modified() {
+        let etag = etag(&get("/xoltia.png").await);
+        let weak = format!("W/{}", etag);
+        let listed = format!("\"other\", {}", etag);
+        for tags in &[&etag[..], &weak, &listed, "*"] {
+            let response = get_if_none_match("/xoltia.png", tags).await;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "If-None-Match: {}", tags);
+            assert_eq!(self::etag(&response), etag);
+            assert!(hyper::body::to_bytes(response.into_body()).await.unwrap().is_empty());
+        }
+
+        let response = get_if_none_match("/xoltia.png", "\"other\"").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get_if_none_match("/xoltia.png?size=6", &etag).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn etags_follow_the_resolved_parameters() {
+        assert_eq!(etag(&get("/xoltia.png?pad=10").await), etag(&get("/xoltia.png?pad=10px+10px").await));
+
+        let default = etag(&get("/xoltia.png").await);
+        for &uri in &["/xoltia.png?size=6", "/xoltia.svg", "/xoltia.png?pad=11", "/xoltia2.png"] {
+            assert_ne!(etag(&get(uri).await), default, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn the_last_of_repeated_parameters_wins() {
+        let body = |response: Response<Body>| async { hyper::body::to_bytes(response.into_body()).await.unwrap() };
+        let repeated = get("/xoltia.svg?size=5&size=7").await;
+        assert_eq!(repeated.status(), StatusCode::OK);
+        assert_eq!(body(repeated).await, body(get("/xoltia.svg?size=7").await).await);
+
+        let error = get_error("/xoltia.svg?strict=1&size=5&size=7").await;
+        assert_eq!(error["problems"][0]["code"], "duplicate_parameter", "{}", error);
+        assert_eq!(error["problems"][0]["parameter"], "size", "{}", error);
+    }
+
+    #[tokio::test]
+    async fn invalid_names_list_the_allowed_values() {
+        let error = get_error("/xoltia.png?strict=1&shape=hexagon").await;
+        assert_eq!(error["parameter"], "shape");
+        assert_eq!(error["allowed"], serde_json::json!(["square", "rounded", "circle", "diamond", "dot"]));
+        assert_eq!(error["problems"][0]["allowed"], error["allowed"]);
+
+        let cases = [("color_model", "rgb"), ("contrast", "none"), ("sym", "yes"), ("mask", "rounded:<radius>"), ("trim", "off")];
+        for &(parameter, name) in &cases {
+            let error = get_error(&format!("/xoltia.png?strict=1&{}=bogus", parameter)).await;
+            assert!(error["allowed"].as_array().unwrap().contains(&name.into()), "{}: {}", parameter, error);
+        }
+
+        let response = get("/xoltia.png?shape=hexagon&sym=sideways").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Ignored-Parameters"], "sym, shape");
+    }
+
+    #[tokio::test]
+    async fn hash_algorithm_header_names_the_digest_used() {
+        let cases = [
+            ("/xoltia.png", "sha224"),
+            ("/xoltia.png?size=14", "sha256"),
+            ("/xoltia.png?cols=18&rows=4", "sha384"),
+            ("/xoltia.png?size=20", "sha512"),
+            ("/xoltia.png?algo=md5", "md5"),
+            ("/xoltia.png?algo=shake256&size=20", "shake256"),
+        ];
+        for &(uri, algorithm) in &cases {
+            let response = get(uri).await;
+            assert_eq!(response.headers()["X-Hash-Algorithm"], algorithm, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn size_errors_name_the_parameter_at_fault() {
+        let cases = [
+            ("/xoltia.png?size=0", "size"),
+            ("/xoltia.png?cols=0", "cols"),
+            ("/xoltia.png?size=5&rows=0", "rows"),
+            ("/xoltia.png?cols=50&res=20", "cols"),
+            ("/xoltia.png?size=50&res=20", "size"),
+            ("/xoltia.png?res=2000", "res"),
+            ("/xoltia.png?res=200&h=2000", "h"),
+            ("/xoltia.png?w=900&pad=100", "pad"),
+            ("/xoltia.ico?res=300", "res"),
+            ("/xoltia.ico?res=200&pad=40", "pad"),
+        ];
+        for &(uri, parameter) in &cases {
+            assert_eq!(get_error(uri).await["parameter"], parameter, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn size_errors_suggest_the_largest_value_that_fits() {
+        let cases = [
+            ("/xoltia.png?size=50&res=20", 20),
+            ("/xoltia.png?res=2000", 1000),
+            ("/xoltia.png?res=2000&pad=100", 800),
+            ("/xoltia.png?res=200&h=2000&pad=0+0+50+0", 950),
+            ("/xoltia.png?res=900&pad=100", 50),
+            ("/xoltia.png?w=900&pad=4294967295", 50),
+            ("/xoltia.ico?res=300", 256),
+            ("/xoltia.ico?res=200&pad=40", 28),
+            ("/xoltia.ico?res=300&pad=20", 216),
+        ];
+        for &(uri, suggested) in &cases {
+            let error = get_error(uri).await;
+            assert_eq!(error["suggested"], suggested, "{}: {}", uri, error);
+            assert_eq!(error["allowed"]["max"], suggested, "{}: {}", uri, error);
+        }
+    }
+}
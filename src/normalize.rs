@@ -0,0 +1,48 @@
+//! Canonicalisation of names before they are hashed.
+
+use std::str::FromStr;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::Error;
+
+/// Case folding applied to names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Case {
+    #[default]
+    Preserve,
+    Lower,
+}
+
+impl Case {
+    /// Every name [`FromStr`] accepts, with the case folding it stands for.
+    pub const NAMES: &'static [(&'static str, Case)] = &[("preserve", Case::Preserve), ("lower", Case::Lower)];
+}
+
+impl FromStr for Case {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::lookup(Case::NAMES, s).ok_or_else(|| Error::InvalidCase(s.to_owned()))
+    }
+}
+
+/// Optional steps applied to names on top of Unicode NFC normalisation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Normalization {
+    pub case: Case,
+    /// Strip leading and trailing whitespace.
+    pub trim: bool,
+}
+
+/// Canonicalises `name` so that equivalent spellings hash the same.
+///
+/// The name is always brought into Unicode normalisation form C, so
+/// precomposed and decomposed accents produce the same identicon.
+pub fn normalize(name: &str, normalization: &Normalization) -> String {
+    let name = if normalization.trim { name.trim() } else { name };
+    match normalization.case {
+        Case::Preserve => name.nfc().collect(),
+        Case::Lower => name.to_lowercase().nfc().collect(),
+    }
+}
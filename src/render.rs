@@ -0,0 +1,597 @@
+//! Painting the cell grid onto a canvas.
+
+use std::str::FromStr;
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::color;
+use crate::grid::{Grid, Symmetry};
+use crate::shape::{Mask, Shape};
+use crate::Error;
+
+/// A distance in pixels, or relative to the side it is measured along.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    /// Percentage in range 0-100.
+    Percent(f32),
+}
+
+impl Length {
+    /// The length in pixels along a side of `side` pixels.
+    pub fn resolve(self, side: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => (side as f32 * percent / 100.0).round() as u32,
+        }
+    }
+}
+
+impl Default for Length {
+    fn default() -> Self {
+        Length::Pixels(0)
+    }
+}
+
+impl FromStr for Length {
+    type Err = ();
+
+    /// Parses a pixel count such as `10` or `10px`, or a percentage such as
+    /// `5%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(percent) = s.strip_suffix('%') {
+            match percent.parse::<f32>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(Length::Percent(percent)),
+                _ => Err(()),
+            }
+        } else {
+            s.strip_suffix("px").unwrap_or(s).parse().map(Length::Pixels).map_err(|_| ())
+        }
+    }
+}
+
+/// Blank border around the grid, as requested.
+///
+/// Percentages of the top and bottom are of the grid height, those of the
+/// left and right of the grid width.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Padding {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl Padding {
+    /// The same padding on every side.
+    pub fn uniform(length: Length) -> Self {
+        Padding {
+            top: length,
+            right: length,
+            bottom: length,
+            left: length,
+        }
+    }
+
+    /// The padding in pixels around a grid of `width` by `height` pixels.
+    pub fn resolve(&self, width: u32, height: u32) -> Insets {
+        Insets {
+            top: self.top.resolve(height),
+            right: self.right.resolve(width),
+            bottom: self.bottom.resolve(height),
+            left: self.left.resolve(width),
+        }
+    }
+}
+
+impl From<u32> for Padding {
+    fn from(pixels: u32) -> Self {
+        Padding::uniform(Length::Pixels(pixels))
+    }
+}
+
+impl FromStr for Padding {
+    type Err = Error;
+
+    /// Parses CSS-like shorthand of one, two or four lengths separated by
+    /// spaces or commas: all sides, vertical and horizontal, or top, right,
+    /// bottom and left.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lengths = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Length>, ()>>()
+            .map_err(|_| Error::InvalidPadding(s.to_owned()))?;
+
+        match *lengths.as_slice() {
+            [all] => Ok(Padding::uniform(all)),
+            [vertical, horizontal] => Ok(Padding {
+                top: vertical,
+                right: horizontal,
+                bottom: vertical,
+                left: horizontal,
+            }),
+            [top, right, bottom, left] => Ok(Padding { top, right, bottom, left }),
+            _ => Err(Error::InvalidPadding(s.to_owned())),
+        }
+    }
+}
+
+/// Blank border around the grid in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Insets {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+/// Gutter between neighbouring cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gap {
+    Pixels(u32),
+    /// Fraction in range 0-1 of the side of a cell.
+    Fraction(f32),
+}
+
+impl Gap {
+    /// The gap in pixels between `cells` cells sharing a side of `side`
+    /// pixels with the gaps between them.
+    pub fn resolve(self, side: u32, cells: u32) -> u32 {
+        match self {
+            Gap::Pixels(pixels) => pixels,
+            Gap::Fraction(fraction) => {
+                let fraction = f64::from(fraction);
+                let cell = f64::from(side) / (f64::from(cells) + f64::from(cells - 1) * fraction);
+                (cell * fraction).round() as u32
+            }
+        }
+    }
+}
+
+impl Default for Gap {
+    fn default() -> Self {
+        Gap::Pixels(0)
+    }
+}
+
+impl FromStr for Gap {
+    type Err = Error;
+
+    /// Parses a pixel count such as `4` or `4px`, or a fraction of the cell
+    /// such as `0.1` or `10%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidGap(s.to_owned());
+        if let Ok(pixels) = s.strip_suffix("px").unwrap_or(s).parse() {
+            return Ok(Gap::Pixels(pixels));
+        }
+        let fraction = match s.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().map_err(|_| invalid())? / 100.0,
+            None => s.parse::<f32>().map_err(|_| invalid())?,
+        };
+        if (0.0..1.0).contains(&fraction) {
+            Ok(Gap::Fraction(fraction))
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Layout of the cell grid inside the canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    /// Number of cells along the horizontal axis.
+    pub columns: u32,
+    /// Number of cells along the vertical axis.
+    pub rows: u32,
+    /// Blank border around the grid.
+    pub padding: Insets,
+    /// Gutter between neighbouring cells in pixels. The outermost cells
+    /// touch the edges of the grid.
+    pub gap: u32,
+    /// Width of the grid in pixels.
+    pub width: u32,
+    /// Height of the grid in pixels.
+    pub height: u32,
+    /// Which cells repeat each other.
+    pub symmetry: Symmetry,
+    /// Outline of the filled cells.
+    pub shape: Shape,
+    /// Outline the whole canvas is clipped to.
+    pub mask: Mask,
+}
+
+impl Layout {
+    /// Width and height of the grid without the gaps between cells.
+    fn cell_area(&self) -> (u32, u32) {
+        let gaps = |cells: u32| (cells - 1).saturating_mul(self.gap);
+        (
+            self.width.saturating_sub(gaps(self.columns)),
+            self.height.saturating_sub(gaps(self.rows)),
+        )
+    }
+
+    /// Width and height of a single cell in whole pixels, rounded down.
+    pub fn cell_size(&self) -> (u32, u32) {
+        let (width, height) = self.cell_area();
+        (width / self.columns, height / self.rows)
+    }
+
+    /// Exact width and height of a single cell in pixels.
+    pub fn cell_extent(&self) -> (f64, f64) {
+        let (width, height) = self.cell_area();
+        (
+            f64::from(width) / f64::from(self.columns),
+            f64::from(height) / f64::from(self.rows),
+        )
+    }
+
+    /// Position of the top-left corner of the cell at column `x`, row `y`
+    /// on the canvas, in pixels.
+    pub fn cell_origin(&self, x: u32, y: u32) -> (f64, f64) {
+        let (width, height) = self.cell_extent();
+        let gap = f64::from(self.gap);
+        (
+            f64::from(self.padding.left) + f64::from(x) * (width + gap),
+            f64::from(self.padding.top) + f64::from(y) * (height + gap),
+        )
+    }
+
+    /// Whether every cell covers a whole number of pixels, so the grid can
+    /// be painted without scaling.
+    pub fn is_exact(&self) -> bool {
+        let (width, height) = self.cell_area();
+        width.is_multiple_of(self.columns) && height.is_multiple_of(self.rows)
+    }
+
+    /// Width and height of the whole canvas, including the padding, in
+    /// pixels. [`IdenticonBuilder::build`](crate::IdenticonBuilder::build)
+    /// rejects layouts whose canvas does not fit into a `u32`.
+    pub fn canvas_size(&self) -> (u32, u32) {
+        let Insets { top, right, bottom, left } = self.padding;
+        (left + self.width + right, top + self.height + bottom)
+    }
+}
+
+/// Fills the `w` by `h` rectangle whose top-left corner is at `x`, `y`.
+pub fn fill_rect(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, c: Rgba<u8>) {
+    let stride = img.width() as usize * 4;
+    let span = x as usize * 4..(x + w) as usize * 4;
+    for row in img.chunks_exact_mut(stride).skip(y as usize).take(h as usize) {
+        fill_span(&mut row[span.clone()], c);
+    }
+}
+
+/// Sets every pixel of the raw RGBA `span` to `c`.
+fn fill_span(span: &mut [u8], c: Rgba<u8>) {
+    for pixel in span.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&c.0);
+    }
+}
+
+/// Draws `shape` anti-aliased over the cell of `width` by `height` pixels
+/// whose top-left corner is at `x`, `y`.
+pub fn fill_shape(img: &mut RgbaImage, x: f64, y: f64, width: f64, height: f64, shape: Shape, c: Rgba<u8>) {
+    Tile::new(shape, x.fract(), y.fract(), width, height).paint(img, x as u32, y as u32, c);
+}
+
+/// The coverage of a shape over every pixel its cell touches.
+struct Tile {
+    width: u32,
+    height: u32,
+    coverage: Vec<f32>,
+}
+
+impl Tile {
+    /// Covers a cell of `width` by `height` pixels whose top-left corner
+    /// lies `x`, `y` into the first pixel of the tile.
+    fn new(shape: Shape, x: f64, y: f64, width: f64, height: f64) -> Tile {
+        let (tile_width, tile_height) = ((x + width).ceil() as u32, (y + height).ceil() as u32);
+        let (center_x, center_y) = (x + width / 2.0, y + height / 2.0);
+        let mut coverage = Vec::with_capacity(tile_width as usize * tile_height as usize);
+        for ty in 0..tile_height {
+            for tx in 0..tile_width {
+                let dx = f64::from(tx) + 0.5 - center_x;
+                let dy = f64::from(ty) + 0.5 - center_y;
+                coverage.push(shape.coverage(dx, dy, width, height));
+            }
+        }
+        Tile {
+            width: tile_width,
+            height: tile_height,
+            coverage,
+        }
+    }
+
+    /// Composites `c` over `img` with the top-left corner of the tile at
+    /// `x`, `y`.
+    fn paint(&self, img: &mut RgbaImage, x: u32, y: u32, c: Rgba<u8>) {
+        let columns = self.width.min(img.width().saturating_sub(x));
+        let rows = self.height.min(img.height().saturating_sub(y));
+        for ty in 0..rows {
+            let coverage = &self.coverage[(ty * self.width) as usize..][..columns as usize];
+            for (tx, &coverage) in (0..columns).zip(coverage) {
+                if coverage >= 1.0 && c[3] == 255 {
+                    img.put_pixel(x + tx, y + ty, c);
+                } else if coverage > 0.0 {
+                    let pixel = img.get_pixel_mut(x + tx, y + ty);
+                    *pixel = color::over(c, coverage, *pixel);
+                }
+            }
+        }
+    }
+}
+
+/// Paints the cells of `grid` onto a canvas filled with `background`,
+/// using the 1-based cell values to index `palette`.
+///
+/// Square cells cover the whole number of pixels given by
+/// [`Layout::cell_size`]; other shapes are drawn at their exact
+/// [`Layout::cell_extent`].
+pub fn render(layout: &Layout, palette: &[Rgba<u8>], background: Rgba<u8>, grid: &Grid) -> RgbaImage {
+    let (width, height) = layout.canvas_size();
+    let background_row = background.0.repeat(width as usize);
+    let mut img = RgbaImage::from_raw(width, height, background_row.repeat(height as usize))
+        .expect("buffer covers the canvas");
+    let fill = |x: u32, y: u32| {
+        let value = grid.cell(i64::from(x), i64::from(y)).unwrap_or(0);
+        palette.get(usize::from(value).wrapping_sub(1)).copied()
+    };
+
+    if layout.shape == Shape::Square {
+        // Every row of cells is drawn into a single scanline, which is then
+        // copied to each pixel row the cells cover.
+        let (cell_width, cell_height) = layout.cell_size();
+        let stride = width as usize * 4;
+        let mut scanline = background_row.clone();
+        for y in 0..grid.rows() {
+            scanline.copy_from_slice(&background_row);
+            let mut filled = false;
+            for x in 0..grid.columns() {
+                if let Some(fill_color) = fill(x, y) {
+                    let left = layout.cell_origin(x, y).0 as usize;
+                    fill_span(&mut scanline[left * 4..(left + cell_width as usize) * 4], fill_color);
+                    filled = true;
+                }
+            }
+            if filled {
+                let top = layout.cell_origin(0, y).1 as usize;
+                for row in img.chunks_exact_mut(stride).skip(top).take(cell_height as usize) {
+                    row.copy_from_slice(&scanline);
+                }
+            }
+        }
+    } else {
+        // Cells of whole pixels all share the same coverage.
+        let (extent_width, extent_height) = layout.cell_extent();
+        let tile = if layout.is_exact() {
+            Some(Tile::new(layout.shape, 0.0, 0.0, extent_width, extent_height))
+        } else {
+            None
+        };
+        for y in 0..grid.rows() {
+            for x in 0..grid.columns() {
+                if let Some(fill_color) = fill(x, y) {
+                    let (px, py) = layout.cell_origin(x, y);
+                    match &tile {
+                        Some(tile) => tile.paint(&mut img, px as u32, py as u32, fill_color),
+                        None => fill_shape(&mut img, px, py, extent_width, extent_height, layout.shape, fill_color),
+                    }
+                }
+            }
+        }
+    }
+
+    img
+}
+
+/// Resampling filter used when the grid width or height is not a multiple
+/// of the number of cells along it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Filter {
+    /// Hard edges, with cells differing in size by up to a pixel.
+    Nearest,
+    /// Area averaging, anti-aliasing the cell edges.
+    #[default]
+    Box,
+    /// Lanczos with a window of 3.
+    Lanczos,
+}
+
+impl Filter {
+    /// Every name [`FromStr`] accepts, with the filter it stands for.
+    pub const NAMES: &'static [(&'static str, Filter)] = &[
+        ("nearest", Filter::Nearest),
+        ("box", Filter::Box),
+        ("lanczos", Filter::Lanczos),
+    ];
+
+    /// The name of the filter as accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Box => "box",
+            Filter::Lanczos => "lanczos",
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::lookup(Filter::NAMES, s).ok_or_else(|| Error::InvalidFilter(s.to_owned()))
+    }
+}
+
+/// Paints the grid like [`render`], at any resolution, and clips the canvas
+/// to the mask of the layout.
+///
+/// Square cells of grids whose width or height is not a multiple of the
+/// number of cells along it are painted at the next larger multiples and
+/// resampled down with `filter`, then placed inside the padding.
+pub fn render_scaled(
+    layout: &Layout,
+    palette: &[Rgba<u8>],
+    background: Rgba<u8>,
+    grid: &Grid,
+    filter: Filter,
+) -> DynamicImage {
+    let mut img = if layout.is_exact() || layout.shape != Shape::Square {
+        render(layout, palette, background, grid)
+    } else {
+        render_resampled(layout, palette, background, grid, filter)
+    };
+    clip(&mut img, layout.mask);
+    DynamicImage::ImageRgba8(img)
+}
+
+fn render_resampled(
+    layout: &Layout,
+    palette: &[Rgba<u8>],
+    background: Rgba<u8>,
+    grid: &Grid,
+    filter: Filter,
+) -> RgbaImage {
+    let (area_width, area_height) = layout.cell_area();
+    let native_side = |area: u32, cells: u32| cells * area.div_ceil(cells) + (cells - 1) * layout.gap;
+    let native = Layout {
+        padding: Insets::default(),
+        width: native_side(area_width, layout.columns),
+        height: native_side(area_height, layout.rows),
+        ..*layout
+    };
+    let painted = render(&native, palette, background, grid);
+    let painted = scale(&painted, layout.width, layout.height, filter);
+
+    let (width, height) = layout.canvas_size();
+    let mut img = RgbaImage::from_pixel(width, height, background);
+    imageops::replace(&mut img, &painted, layout.padding.left, layout.padding.top);
+    img
+}
+
+/// Scales the alpha of every pixel of `img` by how much of it lies inside of
+/// `mask`.
+pub fn clip(img: &mut RgbaImage, mask: Mask) {
+    if mask == Mask::None {
+        return;
+    }
+
+    let (width, height) = (f64::from(img.width()), f64::from(img.height()));
+    for y in 0..img.height() {
+        for x in 0..img.width() {
+            let dx = f64::from(x) + 0.5 - width / 2.0;
+            let dy = f64::from(y) + 0.5 - height / 2.0;
+            let coverage = mask.coverage(dx, dy, width, height);
+            if coverage < 1.0 {
+                let pixel = img.get_pixel_mut(x, y);
+                pixel[3] = (f32::from(pixel[3]) * coverage).round() as u8;
+            }
+        }
+    }
+}
+
+/// Resamples `img` to `width` by `height` pixels.
+///
+/// Colours are weighted by their alpha, so transparent pixels do not darken
+/// the edges of the cells next to them.
+pub fn scale(img: &RgbaImage, width: u32, height: u32, filter: Filter) -> RgbaImage {
+    match filter {
+        Filter::Nearest => imageops::resize(img, width, height, FilterType::Nearest),
+        Filter::Box => unpremultiply(&box_resample(&premultiply(img), width, height)),
+        Filter::Lanczos => {
+            let premultiplied = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+                let [r, g, b, a] = premultiply_pixel(*img.get_pixel(x, y));
+                Rgba([r.round() as u8, g.round() as u8, b.round() as u8, a as u8])
+            });
+            let resized = imageops::resize(&premultiplied, width, height, FilterType::Lanczos3);
+            unpremultiply(&Plane {
+                width,
+                height,
+                pixels: resized.pixels().map(|p| p.0.map(f32::from)).collect(),
+            })
+        }
+    }
+}
+
+/// An image with premultiplied floating point channels.
+struct Plane {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+fn premultiply_pixel(pixel: Rgba<u8>) -> [f32; 4] {
+    let [r, g, b, a] = pixel.0.map(f32::from);
+    let alpha = a / 255.0;
+    [r * alpha, g * alpha, b * alpha, a]
+}
+
+fn premultiply(img: &RgbaImage) -> Plane {
+    Plane {
+        width: img.width(),
+        height: img.height(),
+        pixels: img.pixels().map(|&p| premultiply_pixel(p)).collect(),
+    }
+}
+
+fn unpremultiply(plane: &Plane) -> RgbaImage {
+    RgbaImage::from_fn(plane.width, plane.height, |x, y| {
+        let [r, g, b, a] = plane.pixels[(y * plane.width + x) as usize];
+        let a = a.clamp(0.0, 255.0);
+        let channel = |c: f32| if a > 0.0 { (c * 255.0 / a).round().clamp(0.0, 255.0) as u8 } else { 0 };
+        Rgba([channel(r), channel(g), channel(b), a.round() as u8])
+    })
+}
+
+/// For every destination index, the source indices it covers and the
+/// fraction of the destination pixel each of them makes up.
+fn box_weights(src_len: u32, dst_len: u32) -> Vec<Vec<(usize, f32)>> {
+    let ratio = f64::from(src_len) / f64::from(dst_len);
+    (0..dst_len)
+        .map(|i| {
+            let start = f64::from(i) * ratio;
+            let end = start + ratio;
+            (start.floor() as u32..(end.ceil() as u32).min(src_len))
+                .map(|s| {
+                    let overlap = end.min(f64::from(s) + 1.0) - start.max(f64::from(s));
+                    (s as usize, (overlap / ratio) as f32)
+                })
+                .filter(|&(_, weight)| weight > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+fn box_resample(src: &Plane, width: u32, height: u32) -> Plane {
+    let columns = box_weights(src.width, width);
+    let rows = box_weights(src.height, height);
+    let sum = |weights: &[(usize, f32)], pixel: &dyn Fn(usize) -> [f32; 4]| {
+        weights.iter().fold([0.0; 4], |mut acc, &(i, weight)| {
+            let p = pixel(i);
+            for c in 0..4 {
+                acc[c] += p[c] * weight;
+            }
+            acc
+        })
+    };
+
+    let src_width = src.width as usize;
+    let mut horizontal = Vec::with_capacity(width as usize * src.height as usize);
+    for y in 0..src.height as usize {
+        for weights in &columns {
+            horizontal.push(sum(weights, &|x| src.pixels[y * src_width + x]));
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for weights in &rows {
+        for x in 0..width as usize {
+            pixels.push(sum(weights, &|y| horizontal[y * width as usize + x]));
+        }
+    }
+
+    Plane { width, height, pixels }
+}
@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use hyper::body::Bytes;
+
+/// Encoded identicons by [`Identicon::cache_key`], evicting the least
+/// recently used ones once their keys and bodies exceed `capacity` bytes.
+///
+/// [`Identicon::cache_key`]: identicon_generator::Identicon::cache_key
+pub struct Cache {
+    capacity: usize,
+    entries: Mutex<Lru<Bytes>>,
+    counters: Counters,
+}
+
+/// Hits and misses of a cache.
+#[derive(Default)]
+pub struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// A snapshot of the counters and contents of a cache.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub size: u64,
+}
+
+impl Cache {
+    /// A cache of at most `capacity` bytes. A capacity of 0 disables it.
+    pub fn new(capacity: usize) -> Cache {
+        Cache {
+            capacity,
+            entries: Mutex::new(Lru::default()),
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// The body cached for `key`, marking it as the most recently used.
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let body = self.entries.lock().unwrap().touch(key).cloned();
+        self.counters.record(body.is_some());
+        body
+    }
+
+    /// Caches `body` for `key`. Bodies larger than the whole cache are not
+    /// kept.
+    pub fn insert(&self, key: String, body: Bytes) {
+        // The key is stored in both maps of the LRU.
+        let cost = 2 * key.len() as u64 + body.len() as u64;
+        if cost > self.capacity as u64 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, body, cost);
+        while entries.size() > self.capacity as u64 {
+            entries.evict();
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.counters.stats(&self.entries.lock().unwrap())
+    }
+}
+
+impl Counters {
+    pub fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats<V>(&self, lru: &Lru<V>) -> Stats {
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: lru.len(),
+            size: lru.size(),
+        }
+    }
+}
+
+/// Values by key in order of use, and the total size charged for them.
+pub struct Lru<V> {
+    by_key: HashMap<String, Entry<V>>,
+    /// Keys by the tick they were last used at, oldest first.
+    by_use: BTreeMap<u64, String>,
+    tick: u64,
+    size: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    size: u64,
+    used: u64,
+}
+
+impl<V> Default for Lru<V> {
+    fn default() -> Self {
+        Lru {
+            by_key: HashMap::new(),
+            by_use: BTreeMap::new(),
+            tick: 0,
+            size: 0,
+        }
+    }
+}
+
+impl<V> Lru<V> {
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// The value for `key`, marking it as the most recently used.
+    pub fn touch(&mut self, key: &str) -> Option<&V> {
+        let tick = self.next_tick();
+        let entry = self.by_key.get_mut(key)?;
+        let key = self.by_use.remove(&entry.used).expect("cache entry without a use");
+        self.by_use.insert(tick, key);
+        entry.used = tick;
+        Some(&entry.value)
+    }
+
+    /// Inserts `value` as the most recently used, charging `size` for it.
+    pub fn insert(&mut self, key: String, value: V, size: u64) {
+        let used = self.next_tick();
+        self.size += size;
+        self.by_use.insert(used, key.clone());
+        if let Some(old) = self.by_key.insert(key, Entry { value, size, used }) {
+            self.by_use.remove(&old.used);
+            self.size -= old.size;
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let entry = self.by_key.remove(key)?;
+        self.by_use.remove(&entry.used);
+        self.size -= entry.size;
+        Some(entry.value)
+    }
+
+    /// Removes and returns the least recently used entry.
+    pub fn evict(&mut self) -> Option<(String, V)> {
+        let (_, key) = self.by_use.pop_first()?;
+        let entry = self.by_key.remove(&key).expect("cache use without an entry");
+        self.size -= entry.size;
+        Some((key, entry.value))
+    }
+}
+
+/// A hex digest of `key`, short enough for file names and headers.
+pub fn digest(key: &str) -> String {
+    blake3::hash(key.as_bytes()).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lru(entries: &[(&str, u64)]) -> Lru<u64> {
+        let mut lru = Lru::default();
+        for &(key, size) in entries {
+            lru.insert(key.to_owned(), size, size);
+        }
+        lru
+    }
+
+    #[test]
+    fn insert_charges_the_size_of_each_entry() {
+        let lru = lru(&[("a", 10), ("b", 20), ("c", 5)]);
+        assert_eq!(lru.len(), 3);
+        assert_eq!(lru.size(), 35);
+    }
+
+    #[test]
+    fn replacing_an_entry_charges_only_the_new_size() {
+        let mut lru = lru(&[("a", 10), ("b", 20)]);
+        lru.insert("a".to_owned(), 3, 3);
+        assert_eq!((lru.len(), lru.size()), (2, 23));
+
+        assert_eq!(lru.evict(), Some(("b".to_owned(), 20)));
+        assert_eq!(lru.evict(), Some(("a".to_owned(), 3)));
+        assert_eq!(lru.evict(), None);
+        assert_eq!(lru.size(), 0);
+    }
+
+    #[test]
+    fn touch_defers_eviction() {
+        let mut lru = lru(&[("a", 1), ("b", 2), ("c", 3)]);
+        assert_eq!(lru.touch("a"), Some(&1));
+        assert_eq!(lru.touch("missing"), None);
+
+        let order: Vec<String> = std::iter::from_fn(|| lru.evict()).map(|(key, _)| key).collect();
+        assert_eq!(order, ["b", "c", "a"]);
+        assert_eq!((lru.len(), lru.size()), (0, 0));
+    }
+
+    #[test]
+    fn remove_releases_the_size() {
+        let mut lru = lru(&[("a", 1), ("b", 2)]);
+        assert_eq!(lru.remove("a"), Some(1));
+        assert_eq!(lru.remove("a"), None);
+        assert_eq!((lru.len(), lru.size()), (1, 2));
+        assert_eq!(lru.evict(), Some(("b".to_owned(), 2)));
+    }
+
+    #[test]
+    fn cache_stays_within_its_budget() {
+        // Every entry costs 2 * 1 + 8 = 10 bytes.
+        let cache = Cache::new(25);
+        for key in &["a", "b", "c"] {
+            cache.insert(key.to_string(), Bytes::from(vec![0; 8]));
+        }
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+        assert!(cache.get("c").is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries, stats.size), (2, 1, 2, 20));
+
+        cache.insert("big".to_owned(), Bytes::from(vec![0; 30]));
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use identicon_generator::color::{Bounds, ColorModel, ColorOptions};
+use identicon_generator::hash::Key;
+
+use super::error::ServerError;
+use super::query::Flag;
+
+/// Server-wide defaults, read from the environment at startup.
+///
+/// [`Config::default`] is what an empty environment gives.
+pub struct Config {
+    pub color_options: ColorOptions,
+    /// Reject unknown, duplicate and malformed parameters on every request.
+    pub strict: bool,
+    /// HMAC secrets by version. Names are always keyed when non-empty.
+    keys: Vec<Key>,
+    /// Index into `keys` of the version used unless `keyver` asks otherwise.
+    current_key: usize,
+    /// Number of identicons generated at once.
+    pub workers: usize,
+    /// Number of requests waiting for a worker before new ones are rejected.
+    pub queue_depth: usize,
+    /// Seconds rejected clients are asked to wait before retrying.
+    pub retry_after: u64,
+    /// Bytes of encoded identicons kept in memory, or 0 to disable caching.
+    pub cache_size: usize,
+    /// Directory encoded identicons are kept in across restarts, if any.
+    pub cache_dir: Option<PathBuf>,
+    /// Bytes of encoded identicons kept in `cache_dir`.
+    pub disk_cache_size: u64,
+    /// Number of reads and writes of `cache_dir` at once.
+    pub disk_workers: usize,
+    /// Seconds browsers and proxies may reuse a response for.
+    pub max_age: u64,
+    /// Seconds between logging the cache counters, or 0 to never log them.
+    pub cache_stats_interval: u64,
+}
+
+impl Default for Config {
+    /// The configuration used when no environment variable is set.
+    fn default() -> Config {
+        let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Config {
+            color_options: ColorOptions::default(),
+            strict: false,
+            keys: Vec::new(),
+            current_key: 0,
+            workers,
+            queue_depth: workers * 16,
+            retry_after: 1,
+            cache_size: 64 << 20,
+            cache_dir: None,
+            disk_cache_size: 1 << 30,
+            disk_workers: 4,
+            max_age: 365 * 24 * 60 * 60,
+            cache_stats_interval: 300,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let defaults = Config::default();
+
+        let mut color_options = defaults.color_options;
+        if let Some(model) = parse_env_var::<ColorModel>("COLOR_MODEL") {
+            color_options.model = model;
+        }
+        if let Some(saturation) = parse_env_var::<Bounds>("SATURATION") {
+            color_options.saturation = saturation;
+        }
+        if let Some(lightness) = parse_env_var::<Bounds>("LIGHTNESS") {
+            color_options.lightness = lightness;
+        }
+
+        let mut keys = Vec::new();
+        if let Ok(entries) = std::env::var("HMAC_KEYS") {
+            keys.extend(parse_keys("HMAC_KEYS environment variable", entries.split(',')));
+        }
+        if let Ok(path) = std::env::var("HMAC_KEY_FILE") {
+            let entries = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Could not read HMAC_KEY_FILE {}: {}", path, e));
+            keys.extend(parse_keys("HMAC_KEY_FILE", entries.lines()));
+        }
+
+        let current_version = std::env::var("HMAC_KEY_VERSION").ok();
+
+        let strict = parse_env_var::<Flag>("STRICT").map_or(defaults.strict, |flag| flag.0);
+
+        let workers = parse_env_var("WORKERS").unwrap_or(defaults.workers);
+        if workers == 0 {
+            panic!("WORKERS must be at least 1");
+        }
+        let queue_depth = parse_env_var("QUEUE_DEPTH").unwrap_or(workers * 16);
+        let retry_after = parse_env_var("RETRY_AFTER").unwrap_or(defaults.retry_after);
+        let cache_size = parse_env_var("CACHE_SIZE").unwrap_or(defaults.cache_size);
+        let cache_dir = std::env::var_os("CACHE_DIR").filter(|dir| !dir.is_empty()).map(PathBuf::from);
+        let disk_cache_size = parse_env_var("DISK_CACHE_SIZE").unwrap_or(defaults.disk_cache_size);
+        let disk_workers = parse_env_var("DISK_WORKERS").unwrap_or(defaults.disk_workers);
+        if disk_workers == 0 {
+            panic!("DISK_WORKERS must be at least 1");
+        }
+        let max_age = parse_env_var("CACHE_MAX_AGE").unwrap_or(defaults.max_age);
+        let cache_stats_interval = parse_env_var("CACHE_STATS_INTERVAL").unwrap_or(defaults.cache_stats_interval);
+
+        Config {
+            color_options,
+            strict,
+            workers,
+            queue_depth,
+            retry_after,
+            cache_size,
+            cache_dir,
+            disk_cache_size,
+            disk_workers,
+            max_age,
+            cache_stats_interval,
+            ..defaults
+        }
+        .with_keys(keys, current_version.as_deref())
+    }
+
+    /// Keys names with `keys`. Requests that do not ask for a version use
+    /// the key of `current_version`, or the last one if that is `None`.
+    ///
+    /// Panics if no key has `current_version`.
+    pub fn with_keys(mut self, keys: Vec<Key>, current_version: Option<&str>) -> Config {
+        self.current_key = match current_version {
+            Some(version) => keys.iter()
+                .position(|key| key.version() == version)
+                .unwrap_or_else(|| panic!("HMAC_KEY_VERSION {} does not match any key", version)),
+            None => keys.len().saturating_sub(1),
+        };
+        self.keys = keys;
+        self
+    }
+
+    /// The key for `version`, or the current key if no version is given.
+    /// `Ok(None)` means keyed hashing is disabled.
+    pub fn key(&self, version: Option<&str>) -> Result<Option<&Key>, ServerError> {
+        match version {
+            Some(version) => self.keys.iter().find(|key| key.version() == version).map(Some).ok_or(ServerError::UnknownKeyVersion),
+            None => Ok(self.keys.get(self.current_key)),
+        }
+    }
+}
+
+/// Key versions are sent back in the `X-Key-Version` header.
+fn is_key_version(version: &str) -> bool {
+    !version.is_empty() && version.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+}
+
+/// Parses `version:secret` entries, skipping blank lines and `#` comments.
+/// Whitespace around the version and the secret is ignored. The entries are
+/// never echoed back, so a malformed one cannot leak a secret.
+fn parse_keys<'a, I: Iterator<Item = &'a str>>(source: &str, entries: I) -> Vec<Key> {
+    entries
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+        .enumerate()
+        .map(|(i, entry)| match entry.split_once(':').map(|(version, secret)| (version.trim(), secret.trim())) {
+            Some((version, secret)) if is_key_version(version) && !secret.is_empty() => Key::new(version, secret),
+            _ => panic!("Could not parse key entry {} of {}", i + 1, source),
+        })
+        .collect()
+}
+
+fn parse_env_var<T: FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().map(|value| {
+        value.parse().unwrap_or_else(|_| panic!("Could not parse {} environment variable", key))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_entries_are_trimmed() {
+        let entries = "# rotated yearly\n\n v1: s3cret \n2024-b :other:secret\n";
+        let keys = parse_keys("test", entries.lines());
+        assert_eq!(keys, [Key::new("v1", "s3cret"), Key::new("2024-b", "other:secret")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not parse key entry 1 of test")]
+    fn blank_secrets_are_rejected() {
+        parse_keys("test", ["v1:   "].iter().copied());
+    }
+
+    #[test]
+    fn secrets_are_not_printed() {
+        let keys = parse_keys("test", ["v1: s3cret"].iter().copied());
+        let printed = format!("{:?}", keys);
+        assert!(printed.contains("v1") && !printed.contains("s3cret"), "{}", printed);
+    }
+
+    #[test]
+    fn the_current_key_can_be_chosen() {
+        let keys = || vec![Key::new("v1", "first"), Key::new("v2", "second")];
+        let config = Config::default().with_keys(keys(), None);
+        assert_eq!(config.key(None).unwrap().map(Key::version), Some("v2"));
+        assert_eq!(config.key(Some("v1")).unwrap().map(Key::version), Some("v1"));
+        assert!(config.key(Some("v3")).is_err());
+
+        let config = Config::default().with_keys(keys(), Some("v1"));
+        assert_eq!(config.key(None).unwrap().map(Key::version), Some("v1"));
+        assert_eq!(Config::default().key(None).unwrap(), None);
+    }
+}
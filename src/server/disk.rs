@@ -0,0 +1,286 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use identicon_generator::RENDER_VERSION;
+
+use super::cache::{self, Counters, Lru, Stats};
+
+/// Encoded identicons stored as files named after the hash of their
+/// [`Identicon::cache_key`], so they outlive the process.
+///
+/// Files live in `<dir>/v<RENDER_VERSION>/<first two hex digits>/<rest>`.
+/// Output of other render versions is deleted when the cache is opened,
+/// and the least recently used files are deleted once the cache exceeds
+/// `capacity` bytes. Recency is kept in the modification times of the
+/// files, so it survives restarts.
+///
+/// Every method blocks on the file system.
+///
+/// [`Identicon::cache_key`]: identicon_generator::Identicon::cache_key
+pub struct DiskCache {
+    root: PathBuf,
+    capacity: u64,
+    index: Mutex<Lru<()>>,
+    counters: Counters,
+    /// Distinguishes the temporary files of concurrent writes.
+    writes: AtomicU64,
+}
+
+impl DiskCache {
+    /// Opens the cache in `dir`, creating it if needed, and rebuilds the
+    /// index from the files left by earlier runs.
+    pub fn open(dir: &Path, capacity: u64) -> io::Result<DiskCache> {
+        let version = format!("v{}", RENDER_VERSION);
+        fs::create_dir_all(dir)?;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name != version && is_version_dir(&name) {
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+
+        let root = dir.join(version);
+        let temp = root.join("tmp");
+        if temp.exists() {
+            fs::remove_dir_all(&temp)?;
+        }
+        fs::create_dir_all(&temp)?;
+
+        let mut files = Vec::new();
+        for shard in fs::read_dir(&root)? {
+            let shard = shard?;
+            let prefix = shard.file_name().to_string_lossy().into_owned();
+            if prefix.len() != 2 || !is_hex(&prefix) || !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(shard.path())? {
+                let file = file?;
+                let address = prefix.clone() + &file.file_name().to_string_lossy();
+                let metadata = file.metadata()?;
+                if address.len() == 64 && is_hex(&address) && metadata.is_file() {
+                    files.push((metadata.modified()?, address, metadata.len()));
+                }
+            }
+        }
+        files.sort();
+
+        let cache = DiskCache {
+            root,
+            capacity,
+            index: Mutex::new(Lru::default()),
+            counters: Counters::default(),
+            writes: AtomicU64::new(0),
+        };
+        let mut index = cache.index.lock().unwrap();
+        for (_, address, size) in files {
+            index.insert(address, (), size);
+        }
+        cache.evict(&mut index);
+        drop(index);
+        Ok(cache)
+    }
+
+    /// The body cached for `key`, marking it as the most recently used.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let address = cache::digest(key);
+        let indexed = self.index.lock().unwrap().touch(&address).is_some();
+        let body = if indexed { self.read(&address) } else { None };
+        self.counters.record(body.is_some());
+        body
+    }
+
+    /// Writes `body` for `key`, replacing the file atomically so readers
+    /// never see a partial write.
+    pub fn insert(&self, key: &str, body: &[u8]) {
+        if body.len() as u64 > self.capacity {
+            return;
+        }
+        let address = cache::digest(key);
+        if let Err(e) = self.write(&address, body) {
+            eprintln!("disk cache: could not write {}: {}", address, e);
+            return;
+        }
+        let mut index = self.index.lock().unwrap();
+        index.insert(address, (), body.len() as u64);
+        self.evict(&mut index);
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.counters.stats(&self.index.lock().unwrap())
+    }
+
+    fn path(&self, address: &str) -> PathBuf {
+        self.root.join(&address[..2]).join(&address[2..])
+    }
+
+    fn read(&self, address: &str) -> Option<Vec<u8>> {
+        let path = self.path(address);
+        match fs::read(&path) {
+            Ok(body) => {
+                // Only used to order the startup scan, so a failure is harmless.
+                let _ = File::options().write(true).open(&path).and_then(|f| f.set_modified(SystemTime::now()));
+                Some(body)
+            }
+            Err(e) => {
+                eprintln!("disk cache: could not read {}: {}", address, e);
+                self.index.lock().unwrap().remove(address);
+                None
+            }
+        }
+    }
+
+    fn write(&self, address: &str, body: &[u8]) -> io::Result<()> {
+        let path = self.path(address);
+        fs::create_dir_all(path.parent().unwrap())?;
+        let n = self.writes.fetch_add(1, Ordering::Relaxed);
+        let temp = self.root.join("tmp").join(format!("{}.{}", address, n));
+        let mut file = File::create(&temp)?;
+        file.write_all(body)?;
+        file.sync_data()?;
+        fs::rename(&temp, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+    }
+
+    /// Deletes the least recently used files until the cache fits.
+    fn evict(&self, index: &mut Lru<()>) {
+        while index.size() > self.capacity {
+            if let Some((address, ())) = index.evict() {
+                if let Err(e) = fs::remove_file(self.path(&address)) {
+                    eprintln!("disk cache: could not remove {}: {}", address, e);
+                }
+            }
+        }
+    }
+}
+
+fn is_hex(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn is_version_dir(name: &str) -> bool {
+    name.strip_prefix('v').is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A directory under the system temporary directory, deleted on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("identicon-disk-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn set_age(cache: &DiskCache, key: &str, seconds: u64) {
+        let file = File::options().write(true).open(cache.path(&cache::digest(key))).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(seconds)).unwrap();
+    }
+
+    #[test]
+    fn bodies_survive_a_restart() {
+        let dir = TempDir::new("restart");
+        let cache = DiskCache::open(&dir.0, 100).unwrap();
+        assert_eq!(cache.get("a"), None);
+        cache.insert("a", b"first");
+        assert_eq!(cache.get("a").as_deref(), Some(&b"first"[..]));
+        drop(cache);
+
+        let cache = DiskCache::open(&dir.0, 100).unwrap();
+        assert_eq!(cache.get("a").as_deref(), Some(&b"first"[..]));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries, stats.size), (1, 0, 1, 5));
+    }
+
+    #[test]
+    fn the_startup_scan_orders_files_by_modification_time() {
+        let dir = TempDir::new("scan");
+        let cache = DiskCache::open(&dir.0, 100).unwrap();
+        for key in &["a", "b", "c"] {
+            cache.insert(key, &[0; 10]);
+        }
+        set_age(&cache, "a", 10);
+        set_age(&cache, "b", 30);
+        set_age(&cache, "c", 20);
+        drop(cache);
+
+        // Only two files fit, so the least recently used one goes.
+        let cache = DiskCache::open(&dir.0, 20).unwrap();
+        assert_eq!(cache.stats().entries, 2);
+        assert!(!cache.path(&cache::digest("b")).exists());
+        assert!(cache.get("c").is_some());
+        assert!(cache.get("a").is_some());
+
+        // Reading "a" last makes "c" the next to go.
+        cache.insert("d", &[0; 10]);
+        assert!(cache.get("c").is_none());
+        assert!(!cache.path(&cache::digest("c")).exists());
+        assert!(cache.get("a").is_some() && cache.get("d").is_some());
+    }
+
+    #[test]
+    fn insert_stays_within_the_capacity() {
+        let dir = TempDir::new("capacity");
+        let cache = DiskCache::open(&dir.0, 20).unwrap();
+        cache.insert("a", &[0; 10]);
+        cache.insert("b", &[0; 10]);
+        cache.insert("c", &[0; 10]);
+        assert!(!cache.path(&cache::digest("a")).exists());
+        assert_eq!((cache.stats().entries, cache.stats().size), (2, 20));
+
+        cache.insert("big", &[0; 21]);
+        assert!(!cache.path(&cache::digest("big")).exists());
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn opening_deletes_other_versions_and_temporary_files() {
+        let dir = TempDir::new("versions");
+        let stale = dir.0.join("v0").join("ab");
+        let unrelated = dir.0.join("very-important");
+        let temp = dir.0.join(format!("v{}", RENDER_VERSION)).join("tmp");
+        for path in &[&stale, &unrelated, &temp] {
+            fs::create_dir_all(path).unwrap();
+        }
+        fs::write(stale.join("cd"), b"old").unwrap();
+        fs::write(temp.join("partial.0"), b"half").unwrap();
+
+        let cache = DiskCache::open(&dir.0, 100).unwrap();
+        assert!(!dir.0.join("v0").exists());
+        assert!(unrelated.exists());
+        assert!(temp.exists());
+        assert_eq!(fs::read_dir(&temp).unwrap().count(), 0);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn missing_files_are_dropped_from_the_index() {
+        let dir = TempDir::new("missing");
+        let cache = DiskCache::open(&dir.0, 100).unwrap();
+        cache.insert("a", b"body");
+        fs::remove_file(cache.path(&cache::digest("a"))).unwrap();
+
+        assert_eq!(cache.get("a"), None);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries, stats.size), (0, 1, 0, 0));
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use hyper::{Body, Response, StatusCode};
+use hyper::header::{HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use identicon_generator::{Error, MAX_RESOLUTION};
+use serde_json::{json, Map, Value};
+
+use super::query::Problem;
+
+/// Everything that can go wrong while serving a request.
+#[derive(Debug)]
+pub enum ServerError {
+    MethodNotAllowed,
+    MissingName,
+    UnknownKeyVersion,
+    /// A strict request had unknown, duplicate or malformed parameters.
+    InvalidQuery(Vec<Problem>),
+    /// Every worker is busy and the queue is full. Clients are asked to
+    /// retry after the given number of seconds.
+    Overloaded { retry_after: u64 },
+    /// The identicon could not be built from the value of `parameter`.
+    InvalidParameter { parameter: &'static str, error: Error },
+    /// The generation task panicked.
+    Panic,
+    /// The response could not be assembled.
+    Response(hyper::http::Error),
+    Identicon(Error),
+}
+
+impl ServerError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ServerError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ServerError::MissingName => StatusCode::NOT_FOUND,
+            ServerError::UnknownKeyVersion | ServerError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            ServerError::Overloaded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ServerError::Panic | ServerError::Response(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ServerError::InvalidParameter { .. } => StatusCode::BAD_REQUEST,
+            ServerError::Identicon(e) if e.is_invalid_input() => StatusCode::BAD_REQUEST,
+            ServerError::Identicon(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// A stable, machine-readable identifier sent in the `X-Error-Code`
+    /// header.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServerError::MethodNotAllowed => "method_not_allowed",
+            ServerError::MissingName => "missing_name",
+            ServerError::UnknownKeyVersion => "unknown_key_version",
+            ServerError::InvalidQuery(_) => "invalid_query",
+            ServerError::Overloaded { .. } => "overloaded",
+            ServerError::Panic => "internal_error",
+            ServerError::Response(_) => "response_failed",
+            ServerError::InvalidParameter { error, .. } | ServerError::Identicon(error) => error.code(),
+        }
+    }
+
+    /// The query parameter at fault, the values it accepts and a value
+    /// that would have been accepted, where known.
+    pub fn detail(&self) -> (Option<&str>, Option<Value>, Option<Value>) {
+        let (parameter, error) = match self {
+            ServerError::UnknownKeyVersion => return (Some("keyver"), None, None),
+            ServerError::InvalidQuery(problems) => {
+                let allowed = problems.first().and_then(|problem| match problem {
+                    Problem::Invalid { allowed, .. } => allowed.clone(),
+                    _ => None,
+                });
+                return (problems.first().map(Problem::parameter), allowed, None);
+            }
+            ServerError::InvalidParameter { parameter, error } => (Some(*parameter), error),
+            ServerError::Identicon(error) => (None, error),
+            _ => return (None, None, None),
+        };
+
+        // The largest value of the parameter at fault that keeps the canvas
+        // within `limit`, given the side and padding it was built with.
+        let fit = |limit: u32, side: u32, padding: u32| {
+            let (min, max) = match parameter {
+                Some("pad") => (0, limit.saturating_sub(side) / 2),
+                _ => (1, limit.saturating_sub(padding)),
+            };
+            if max < min {
+                return ("res", None, None);
+            }
+            ("res", Some(json!({ "min": min, "max": max })), Some(json!(max)))
+        };
+        let (default, allowed, suggested) = match *error {
+            Error::GridSize(_) => ("size", Some(json!({ "min": 1 })), Some(json!(5))),
+            Error::GridLargerThanResolution { resolution, .. } => {
+                ("size", Some(json!({ "min": 1, "max": resolution })), Some(json!(resolution)))
+            }
+            Error::ResolutionTooLarge { side, padding } => fit(MAX_RESOLUTION, side, padding),
+            Error::IcoTooLarge { side, padding } => fit(256, side, padding),
+            Error::ColorCount(_) => ("colors", Some(json!({ "min": 2, "max": 4 })), Some(json!(2))),
+            Error::GapTooLarge(_) => ("gap", None, Some(json!(0))),
+            _ => return (None, None, None),
+        };
+        (Some(parameter.unwrap_or(default)), allowed, suggested)
+    }
+
+    /// The JSON error document.
+    pub fn to_json(&self) -> Value {
+        let (parameter, allowed, suggested) = self.detail();
+        let mut error = Map::new();
+        error.insert("code".to_owned(), json!(self.code()));
+        error.insert("message".to_owned(), json!(self.to_string()));
+        if let Some(parameter) = parameter {
+            error.insert("parameter".to_owned(), json!(parameter));
+        }
+        if let Some(allowed) = allowed {
+            error.insert("allowed".to_owned(), allowed);
+        }
+        if let Some(suggested) = suggested {
+            error.insert("suggested".to_owned(), suggested);
+        }
+        if let ServerError::InvalidQuery(problems) = self {
+            let problems = problems.iter()
+                .map(|problem| {
+                    let mut entry = json!({
+                        "code": problem.code(),
+                        "message": problem.to_string(),
+                        "parameter": problem.parameter(),
+                    });
+                    if let Problem::Invalid { allowed: Some(allowed), .. } = problem {
+                        entry["allowed"] = allowed.clone();
+                    }
+                    entry
+                })
+                .collect();
+            error.insert("problems".to_owned(), Value::Array(problems));
+        }
+        json!({ "error": error })
+    }
+
+    /// Builds the error response, as a JSON document if `json` is set and as
+    /// plain text otherwise. This cannot fail, so it is also used to report
+    /// failures of building the regular response.
+    pub fn into_response(self, json: bool) -> Response<Body> {
+        let (body, content_type) = if json {
+            (self.to_json().to_string(), "application/json")
+        } else {
+            (self.to_string(), "text/plain; charset=utf-8")
+        };
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = self.status();
+
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers.insert("X-Error-Code", HeaderValue::from_static(self.code()));
+        if let ServerError::Overloaded { retry_after } = self {
+            headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+
+        response
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::MethodNotAllowed => write!(f, "Only GET requests are supported"),
+            ServerError::MissingName => write!(f, "No name was provided"),
+            ServerError::UnknownKeyVersion => write!(f, "Unknown key version"),
+            ServerError::InvalidQuery(problems) => {
+                write!(f, "Invalid query")?;
+                for (i, problem) in problems.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, problem)?;
+                }
+                Ok(())
+            }
+            ServerError::Overloaded { .. } => write!(f, "Too many identicons are being generated, try again later"),
+            ServerError::Panic => write!(f, "Internal error while generating the identicon"),
+            ServerError::Response(e) => write!(f, "Unable to build the response: {}", e),
+            ServerError::InvalidParameter { error, .. } | ServerError::Identicon(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<Error> for ServerError {
+    fn from(e: Error) -> Self {
+        ServerError::Identicon(e)
+    }
+}
+
+impl From<hyper::http::Error> for ServerError {
+    fn from(e: hyper::http::Error) -> Self {
+        ServerError::Response(e)
+    }
+}
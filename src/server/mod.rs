@@ -0,0 +1,39 @@
+//! The HTTP adapter around the identicon library.
+
+pub mod cache;
+pub mod config;
+pub mod disk;
+pub mod error;
+pub mod pool;
+pub mod query;
+
+use cache::Cache;
+use config::Config;
+use disk::DiskCache;
+use pool::Pool;
+
+/// Everything shared between requests.
+pub struct State {
+    pub config: Config,
+    pub pool: Pool,
+    pub cache: Cache,
+    pub disk: Option<DiskCache>,
+    /// Runs the reads and writes of `disk`. Reads that find it saturated
+    /// count as misses and writes are skipped.
+    pub disk_io: Pool,
+}
+
+impl State {
+    pub fn new(config: Config) -> State {
+        State {
+            pool: Pool::new(config.workers, config.queue_depth, config.retry_after),
+            cache: Cache::new(config.cache_size),
+            disk: config.cache_dir.as_ref().map(|dir| {
+                DiskCache::open(dir, config.disk_cache_size)
+                    .unwrap_or_else(|e| panic!("Could not open CACHE_DIR {}: {}", dir.display(), e))
+            }),
+            disk_io: Pool::new(config.disk_workers, config.disk_workers * 16, config.retry_after),
+            config,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+use tokio::task;
+
+use super::error::ServerError;
+
+/// Runs generation jobs on the blocking thread pool, so encoding large
+/// images never stalls the threads serving connections.
+///
+/// At most `workers` jobs run at once and at most `queue_depth` more wait
+/// for a worker. Jobs beyond that are turned away with
+/// [`ServerError::Overloaded`] instead of queueing without bound.
+pub struct Pool {
+    workers: Arc<Semaphore>,
+    admitted: Arc<Semaphore>,
+    retry_after: u64,
+}
+
+impl Pool {
+    pub fn new(workers: usize, queue_depth: usize, retry_after: u64) -> Pool {
+        Pool {
+            workers: Arc::new(Semaphore::new(workers)),
+            admitted: Arc::new(Semaphore::new(workers + queue_depth)),
+            retry_after,
+        }
+    }
+
+    /// Runs `job` once a worker is free. A panicking job is reported as
+    /// [`ServerError::Panic`].
+    ///
+    /// The job holds on to its worker until it finishes, even if the
+    /// returned future is dropped because the client went away.
+    pub async fn run<F, T>(&self, job: F) -> Result<T, ServerError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let admitted = self.admitted
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| ServerError::Overloaded { retry_after: self.retry_after })?;
+        let worker = self.workers.clone().acquire_owned().await;
+        task::spawn_blocking(move || {
+            let _permits = (admitted, worker);
+            job()
+        })
+        .await
+        .map_err(|_| ServerError::Panic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use futures::future::{self, Either};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn dropped_request_keeps_its_slot_until_the_job_finishes() {
+        let pool = Pool::new(1, 0, 1);
+        let (started, wait_for_start) = mpsc::channel();
+        let (release, wait_for_release) = mpsc::channel::<()>();
+
+        // Hang up as soon as the job is running.
+        let run = pool.run(move || {
+            started.send(()).unwrap();
+            wait_for_release.recv().unwrap();
+        });
+        let start = task::spawn_blocking(move || wait_for_start.recv().unwrap());
+        match future::select(Box::pin(run), start).await {
+            Either::Left(_) => panic!("job finished before it was released"),
+            Either::Right((_, run)) => drop(run),
+        }
+
+        assert!(matches!(pool.run(|| ()).await, Err(ServerError::Overloaded { .. })));
+
+        release.send(()).unwrap();
+        for _ in 0..100 {
+            if pool.run(|| ()).await.is_ok() {
+                return;
+            }
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        panic!("the slot was not released after the job finished");
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use hyper::{Body, Request};
+use hyper::header::{ACCEPT, IF_NONE_MATCH};
+use identicon_generator::color::{ColorModel, Contrast};
+use identicon_generator::grid::Symmetry;
+use identicon_generator::hash::Algorithm;
+use identicon_generator::normalize::Case;
+use identicon_generator::render::Filter;
+use identicon_generator::shape::{Mask, Shape};
+use identicon_generator::MAX_RESOLUTION;
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Value};
+
+/// Percent-decodes `s`, replacing invalid UTF-8 with U+FFFD.
+pub fn decode(s: &str) -> String {
+    percent_decode_str(s).decode_utf8_lossy().into_owned()
+}
+
+/// A problem with a single query parameter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    Unknown(String),
+    Duplicate(String),
+    /// A value that could not be parsed, with the values the parameter
+    /// accepts where they can be listed.
+    Invalid { parameter: String, value: String, reason: String, allowed: Option<Value> },
+}
+
+impl Problem {
+    pub fn parameter(&self) -> &str {
+        match self {
+            Problem::Unknown(parameter) | Problem::Duplicate(parameter) => parameter,
+            Problem::Invalid { parameter, .. } => parameter,
+        }
+    }
+
+    /// A stable, machine-readable identifier for the kind of problem.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Problem::Unknown(_) => "unknown_parameter",
+            Problem::Duplicate(_) => "duplicate_parameter",
+            Problem::Invalid { .. } => "invalid_value",
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Unknown(parameter) => write!(f, "Unknown parameter {}", parameter),
+            Problem::Duplicate(parameter) => write!(f, "Parameter {} was given more than once", parameter),
+            Problem::Invalid { parameter, value, reason, .. } => {
+                write!(f, "Invalid value {:?} for {}: {}", value, parameter, reason)
+            }
+        }
+    }
+}
+
+/// The decoded query parameters of a request.
+///
+/// Lookups fall back to their default when a value cannot be parsed, but
+/// every parameter that was missed or malformed is recorded so that strict
+/// requests can be rejected by [`Params::problems`].
+#[derive(Debug, Default)]
+pub struct Params {
+    values: HashMap<String, String>,
+    duplicates: Vec<String>,
+    read: HashSet<String>,
+    invalid: Vec<Problem>,
+}
+
+impl Params {
+    /// Decodes an `application/x-www-form-urlencoded` query string.
+    ///
+    /// A parameter without `=` has an empty value. The last occurrence of a
+    /// repeated parameter wins, as it did before strict validation, but the
+    /// repetition is recorded as a problem.
+    pub fn parse(query: &str) -> Params {
+        let mut params = Params::default();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = decode(&key.replace('+', " "));
+            let value = decode(&value.replace('+', " "));
+            match params.values.entry(key) {
+                Entry::Occupied(mut entry) => {
+                    if !params.duplicates.contains(entry.key()) {
+                        params.duplicates.push(entry.key().clone());
+                    }
+                    entry.insert(value);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+            }
+        }
+        params
+    }
+
+    /// The parsed value of `key`, or `None` if it is missing or malformed.
+    pub fn get<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.read.insert(key.to_owned());
+        let value = self.values.get(key)?;
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                self.invalid.push(Problem::Invalid {
+                    parameter: key.to_owned(),
+                    value: value.clone(),
+                    reason: e.to_string(),
+                    allowed: allowed_values(key),
+                });
+                None
+            }
+        }
+    }
+
+    /// The parsed value of `key`, or `default` if it is missing or malformed.
+    pub fn get_or<T>(&mut self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// Every duplicate, malformed or never read parameter. Call this after
+    /// all parameters have been looked up.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut unknown: Vec<&String> = self.values.keys().filter(|key| !self.read.contains(*key)).collect();
+        unknown.sort();
+
+        unknown.into_iter().map(|key| Problem::Unknown(key.clone()))
+            .chain(self.duplicates.iter().map(|key| Problem::Duplicate(key.clone())))
+            .chain(self.invalid.iter().cloned())
+            .collect()
+    }
+}
+
+/// The values `parameter` accepts, as a list of names or a numeric range,
+/// where they can be listed.
+pub fn allowed_values(parameter: &str) -> Option<Value> {
+    let allowed = match parameter {
+        "strict" | "trim" => names(Flag::NAMES),
+        "algo" => names(Algorithm::NAMES),
+        "case" => names(Case::NAMES),
+        "color_model" => names(ColorModel::NAMES),
+        "contrast" => names(Contrast::NAMES),
+        "sym" => names(Symmetry::NAMES),
+        "shape" => names(Shape::NAMES),
+        "mask" => {
+            let mut masks = names(Mask::NAMES);
+            masks.as_array_mut().unwrap().push(json!("rounded:<radius>"));
+            masks
+        }
+        "filter" => names(Filter::NAMES),
+        "size" | "cols" | "rows" => json!({ "min": 1 }),
+        "res" | "w" | "h" => json!({ "min": 1, "max": MAX_RESOLUTION }),
+        "colors" => json!({ "min": 2, "max": 4 }),
+        _ => return None,
+    };
+    Some(allowed)
+}
+
+/// The names of a `NAMES` table, leaving out the empty one a bare key
+/// stands for.
+fn names<T>(table: &[(&str, T)]) -> Value {
+    table.iter().map(|&(name, _)| name).filter(|name| !name.is_empty()).collect()
+}
+
+/// A boolean parameter, accepting `true/false`, `1/0`, `yes/no` and
+/// `on/off`. A bare key without a value counts as `true`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flag(pub bool);
+
+impl Flag {
+    /// Every spelling [`FromStr`] accepts, with the value it stands for.
+    pub const NAMES: &'static [(&'static str, Flag)] = &[
+        ("true", Flag(true)),
+        ("false", Flag(false)),
+        ("1", Flag(true)),
+        ("0", Flag(false)),
+        ("yes", Flag(true)),
+        ("no", Flag(false)),
+        ("on", Flag(true)),
+        ("off", Flag(false)),
+        ("", Flag(true)),
+    ];
+}
+
+impl FromStr for Flag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Flag::NAMES.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|&(_, flag)| flag)
+            .ok_or_else(|| "expected one of true/false, 1/0, yes/no or on/off".to_owned())
+    }
+}
+
+/// Splits the last path segment into the decoded name and extension.
+pub fn parse_file_name(path: &str) -> Option<(String, Option<String>)> {
+    let segment = decode(path.rsplit('/').next().unwrap_or(""));
+    if segment.is_empty() {
+        return None;
+    }
+
+    match segment.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => Some((stem.to_owned(), Some(extension.to_owned()))),
+        _ => Some((segment, None)),
+    }
+}
+
+/// Whether the client listed `application/json` in its `Accept` header.
+pub fn accepts_json(req: &Request<Body>) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_range| {
+            let media_type = media_range.split(';').next().unwrap_or("").trim();
+            media_type.eq_ignore_ascii_case("application/json")
+        })
+}
+
+/// Whether the `If-None-Match` header of the request matches `etag`, using
+/// the weak comparison RFC 7232 prescribes for it.
+pub fn matches_etag(req: &Request<Body>, etag: &str) -> bool {
+    req.headers()
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque_tag(tag) == opaque_tag(etag))
+}
+
+/// An entity tag without its weakness indicator.
+fn opaque_tag(tag: &str) -> &str {
+    let tag = tag.trim();
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
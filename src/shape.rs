@@ -0,0 +1,189 @@
+//! The shape filled cells are drawn as, and the shape the whole canvas is
+//! clipped to.
+
+use std::str::FromStr;
+
+use crate::render::Length;
+use crate::Error;
+
+/// Outline of a filled cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Shape {
+    /// The whole cell, so neighbouring cells merge into solid blocks.
+    #[default]
+    Square,
+    /// The whole cell with rounded corners.
+    Rounded,
+    /// The largest circle that fits into the cell.
+    Circle,
+    /// A square turned by 45 degrees, touching the middle of every side of
+    /// the cell.
+    Diamond,
+    /// A circle of half the diameter of [`Shape::Circle`].
+    Dot,
+}
+
+impl Shape {
+    /// Every name [`FromStr`] accepts, with the shape it stands for.
+    pub const NAMES: &'static [(&'static str, Shape)] = &[
+        ("square", Shape::Square),
+        ("rounded", Shape::Rounded),
+        ("circle", Shape::Circle),
+        ("diamond", Shape::Diamond),
+        ("dot", Shape::Dot),
+    ];
+
+    /// The name of the shape as accepted by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Square => "square",
+            Shape::Rounded => "rounded",
+            Shape::Circle => "circle",
+            Shape::Diamond => "diamond",
+            Shape::Dot => "dot",
+        }
+    }
+
+    /// The corner radius of [`Shape::Rounded`], or the radius of the
+    /// circular shapes, in a cell of `width` by `height` pixels.
+    pub fn radius(self, width: f64, height: f64) -> f64 {
+        let side = width.min(height);
+        match self {
+            Shape::Square | Shape::Diamond => 0.0,
+            Shape::Rounded => side * 0.2,
+            Shape::Circle => side * 0.5,
+            Shape::Dot => side * 0.25,
+        }
+    }
+
+    /// Signed distance in pixels from the point `x`, `y` to the outline of
+    /// the shape, negative inside of it. The point is relative to the centre
+    /// of a cell of `width` by `height` pixels.
+    pub fn distance(self, x: f64, y: f64, width: f64, height: f64) -> f64 {
+        let (x, y) = (x.abs(), y.abs());
+        let (half_width, half_height) = (width / 2.0, height / 2.0);
+        let radius = self.radius(width, height);
+        match self {
+            Shape::Square | Shape::Rounded => {
+                let qx = x - (half_width - radius);
+                let qy = y - (half_height - radius);
+                qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+            }
+            Shape::Circle | Shape::Dot => x.hypot(y) - radius,
+            Shape::Diamond => {
+                (x * half_height + y * half_width - half_width * half_height) / half_width.hypot(half_height)
+            }
+        }
+    }
+
+    /// Approximate fraction of the pixel centred on `x`, `y` that the shape
+    /// covers, for anti-aliasing. See [`Shape::distance`].
+    pub fn coverage(self, x: f64, y: f64, width: f64, height: f64) -> f32 {
+        (0.5 - self.distance(x, y, width, height)).clamp(0.0, 1.0) as f32
+    }
+}
+
+impl FromStr for Shape {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::lookup(Shape::NAMES, s).ok_or_else(|| Error::InvalidShape(s.to_owned()))
+    }
+}
+
+/// Outline the whole canvas, including the padding, is clipped to.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mask {
+    /// The canvas is left rectangular.
+    #[default]
+    None,
+    /// The largest ellipse that fits into the canvas, a circle for square
+    /// canvases.
+    Circle,
+    /// A superellipse of degree 4, between a circle and a square.
+    Squircle,
+    /// The canvas with rounded corners. Percentages are of the shorter side,
+    /// and the radius is at most half of it.
+    Rounded(Length),
+}
+
+impl Mask {
+    /// Every name [`FromStr`] accepts on its own, with the mask it stands
+    /// for. [`Mask::Rounded`] is spelled `rounded:<radius>` instead.
+    pub const NAMES: &'static [(&'static str, Mask)] = &[
+        ("none", Mask::None),
+        ("circle", Mask::Circle),
+        ("squircle", Mask::Squircle),
+    ];
+
+    /// Signed distance in pixels from the point `x`, `y` to the outline of
+    /// the mask, negative inside of it. The point is relative to the centre
+    /// of a canvas of `width` by `height` pixels.
+    ///
+    /// The distance is exact for circles and rounded rectangles and a first
+    /// order approximation elsewhere, which is close enough near the outline.
+    pub fn distance(self, x: f64, y: f64, width: f64, height: f64) -> f64 {
+        let (x, y) = (x.abs(), y.abs());
+        let (a, b) = (width / 2.0, height / 2.0);
+        match self {
+            Mask::None => f64::NEG_INFINITY,
+            Mask::Circle => {
+                let f = (x / a).hypot(y / b);
+                let gradient = (x / (a * a)).hypot(y / (b * b)) / f;
+                if f > 0.0 { (f - 1.0) / gradient } else { -a.min(b) }
+            }
+            Mask::Squircle => {
+                let (u, v) = (x / a, y / b);
+                let f = (u.powi(4) + v.powi(4)).powf(0.25);
+                let gradient = (u.powi(3) / a).hypot(v.powi(3) / b) / f.powi(3);
+                if f > 0.0 { (f - 1.0) / gradient } else { -a.min(b) }
+            }
+            Mask::Rounded(_) => {
+                let radius = self.radius(width, height).unwrap_or(0.0);
+                let qx = x - (a - radius);
+                let qy = y - (b - radius);
+                qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+            }
+        }
+    }
+
+    /// The corner radius of [`Mask::Rounded`] on a canvas of `width` by
+    /// `height` pixels.
+    pub fn radius(self, width: f64, height: f64) -> Option<f64> {
+        match self {
+            Mask::Rounded(radius) => {
+                let side = width.min(height);
+                let radius = match radius {
+                    Length::Pixels(pixels) => f64::from(pixels),
+                    Length::Percent(percent) => side * f64::from(percent) / 100.0,
+                };
+                Some(radius.min(side / 2.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// Approximate fraction of the pixel centred on `x`, `y` inside of the
+    /// mask, for anti-aliasing. See [`Mask::distance`].
+    pub fn coverage(self, x: f64, y: f64, width: f64, height: f64) -> f32 {
+        (0.5 - self.distance(x, y, width, height)).clamp(0.0, 1.0) as f32
+    }
+}
+
+impl FromStr for Mask {
+    type Err = Error;
+
+    /// Parses a mask name, with the radius of rounded masks after a colon
+    /// such as `rounded:16` or `rounded:10%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((name, radius)) if name.eq_ignore_ascii_case("rounded") => radius
+                .to_ascii_lowercase()
+                .parse()
+                .map(Mask::Rounded)
+                .map_err(|_| Error::InvalidMask(s.to_owned())),
+            Some(_) => Err(Error::InvalidMask(s.to_owned())),
+            None => crate::lookup(Mask::NAMES, s).ok_or_else(|| Error::InvalidMask(s.to_owned())),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Vector output.
+//!
+//! Filled square cells without gaps between them are merged into outlines so
+//! every colour is drawn by a single `<path>` regardless of how many cells
+//! it covers. Other cells are drawn as one element each, grouped by colour.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use image::Rgba;
+
+use crate::grid::Grid;
+use crate::render::Layout;
+use crate::shape::{Mask, Shape};
+
+type Point = (u32, u32);
+
+/// Traces the outlines of the cells of `grid` holding `value`.
+///
+/// Each outline is a closed loop of grid vertices with collinear vertices
+/// removed. Outer boundaries run clockwise and holes counter-clockwise, so
+/// the outlines fill correctly with the `nonzero` rule.
+pub fn outlines(grid: &Grid, value: u8) -> Vec<Vec<Point>> {
+    let mut edges: BTreeMap<Point, Vec<Point>> = BTreeMap::new();
+    let mut add_edge = |from: Point, to: Point| edges.entry(from).or_default().push(to);
+    let matches = |x: i64, y: i64| grid.cell(x, y) == Some(value);
+
+    for y in 0..grid.rows() {
+        for x in 0..grid.columns() {
+            let (cx, cy) = (i64::from(x), i64::from(y));
+            if !matches(cx, cy) {
+                continue;
+            }
+            if !matches(cx, cy - 1) {
+                add_edge((x, y), (x + 1, y));
+            }
+            if !matches(cx + 1, cy) {
+                add_edge((x + 1, y), (x + 1, y + 1));
+            }
+            if !matches(cx, cy + 1) {
+                add_edge((x + 1, y + 1), (x, y + 1));
+            }
+            if !matches(cx - 1, cy) {
+                add_edge((x, y + 1), (x, y));
+            }
+        }
+    }
+
+    let mut outlines = Vec::new();
+    while let Some(&start) = edges.keys().next() {
+        let mut outline = vec![start];
+        let mut current = start;
+        loop {
+            let targets = edges.get_mut(&current).expect("outline is always closed");
+            let next = targets.pop().expect("outline is always closed");
+            if targets.is_empty() {
+                edges.remove(&current);
+            }
+            if next == start {
+                break;
+            }
+            outline.push(next);
+            current = next;
+        }
+        outlines.push(remove_collinear(outline));
+    }
+
+    outlines
+}
+
+fn remove_collinear(outline: Vec<Point>) -> Vec<Point> {
+    let n = outline.len();
+    (0..n)
+        .filter(|&i| {
+            let (px, py) = outline[(i + n - 1) % n];
+            let (x, y) = outline[i];
+            let (nx, ny) = outline[(i + 1) % n];
+            !((px == x && x == nx) || (py == y && y == ny))
+        })
+        .map(|i| outline[i])
+        .collect()
+}
+
+/// Builds the path data for `outlines`, scaling grid vertices to pixels.
+pub fn path_data(outlines: &[Vec<Point>], layout: &Layout) -> String {
+    let (cell_width, cell_height) = layout.cell_extent();
+    let to_pixel = |v: u32, offset: u32, extent: f64| round(f64::from(offset) + f64::from(v) * extent);
+    let to_x = |x: u32| to_pixel(x, layout.padding.left, cell_width);
+    let to_y = |y: u32| to_pixel(y, layout.padding.top, cell_height);
+    let mut d = String::new();
+
+    for outline in outlines {
+        let mut points = outline.iter();
+        let (x, y) = match points.next() {
+            Some(&start) => start,
+            None => continue,
+        };
+        let (mut last_x, mut last_y) = (x, y);
+        write!(d, "M{} {}", to_x(x), to_y(y)).unwrap();
+        for &(x, y) in points {
+            if x != last_x {
+                write!(d, "H{}", to_x(x)).unwrap();
+            } else if y != last_y {
+                write!(d, "V{}", to_y(y)).unwrap();
+            }
+            last_x = x;
+            last_y = y;
+        }
+        d.push('Z');
+    }
+
+    d
+}
+
+/// Draws every cell of `grid` holding `value` as an element of its own, in
+/// the [`Shape`] of the layout.
+///
+/// Square cells are only drawn this way when there are gaps between them,
+/// otherwise [`outlines`] are more compact.
+pub fn shape_elements(grid: &Grid, value: u8, layout: &Layout) -> String {
+    let (cell_width, cell_height) = layout.cell_extent();
+    let radius = round(layout.shape.radius(cell_width, cell_height));
+    let mut elements = String::new();
+
+    for y in 0..grid.rows() {
+        for x in 0..grid.columns() {
+            if grid.cell(i64::from(x), i64::from(y)) != Some(value) {
+                continue;
+            }
+            let (left, top) = layout.cell_origin(x, y);
+            let (center_x, center_y) = (round(left + cell_width / 2.0), round(top + cell_height / 2.0));
+            let (right, bottom) = (round(left + cell_width), round(top + cell_height));
+            let (left, top) = (round(left), round(top));
+            match layout.shape {
+                Shape::Square => write!(
+                    elements,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                    left,
+                    top,
+                    round(right - left),
+                    round(bottom - top)
+                ),
+                Shape::Rounded => write!(
+                    elements,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>",
+                    left,
+                    top,
+                    round(right - left),
+                    round(bottom - top),
+                    radius
+                ),
+                Shape::Circle | Shape::Dot => {
+                    write!(elements, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\"/>", center_x, center_y, radius)
+                }
+                Shape::Diamond => write!(
+                    elements,
+                    "<path d=\"M{} {}L{} {}L{} {}L{} {}Z\"/>",
+                    center_x, top, right, center_y, center_x, bottom, left, center_y
+                ),
+            }
+            .unwrap();
+        }
+    }
+
+    elements
+}
+
+/// Writes the SVG document for `grid` on top of `background`, with one path
+/// or group of shapes per entry of `palette`.
+pub fn document(layout: &Layout, grid: &Grid, palette: &[Rgba<u8>], background: Rgba<u8>) -> String {
+    let (width, height) = layout.canvas_size();
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\"",
+        width, height
+    );
+    if layout.shape == Shape::Square {
+        svg.push_str(" shape-rendering=\"crispEdges\"");
+    }
+    svg.push('>');
+
+    if let Some(outline) = mask_outline(layout.mask, f64::from(width), f64::from(height)) {
+        write!(svg, "<defs><clipPath id=\"mask\">{}</clipPath></defs><g clip-path=\"url(#mask)\">", outline).unwrap();
+    }
+
+    if background[3] != 0 {
+        write!(svg, "<rect width=\"{}\" height=\"{}\"{}/>", width, height, fill(background)).unwrap();
+    }
+
+    for (i, &color) in palette.iter().enumerate() {
+        let value = i as u8 + 1;
+        if layout.shape == Shape::Square && layout.gap == 0 {
+            let d = path_data(&outlines(grid, value), layout);
+            if !d.is_empty() {
+                write!(svg, "<path{} d=\"{}\"/>", fill(color), d).unwrap();
+            }
+        } else {
+            let elements = shape_elements(grid, value, layout);
+            if !elements.is_empty() {
+                write!(svg, "<g{}>{}</g>", fill(color), elements).unwrap();
+            }
+        }
+    }
+
+    if layout.mask != Mask::None {
+        svg.push_str("</g>");
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// The element `mask` clips a canvas of `width` by `height` pixels with,
+/// or `None` if the canvas is not clipped.
+fn mask_outline(mask: Mask, width: f64, height: f64) -> Option<String> {
+    let (a, b) = (width / 2.0, height / 2.0);
+    let attributes = " shape-rendering=\"geometricPrecision\"";
+    let outline = match mask {
+        Mask::None => return None,
+        Mask::Circle => format!(
+            "<ellipse cx=\"{0}\" cy=\"{1}\" rx=\"{0}\" ry=\"{1}\"{2}/>",
+            round(a),
+            round(b),
+            attributes
+        ),
+        Mask::Squircle => {
+            // The superellipse |x|^4 + |y|^4 = 1, traced as a polygon.
+            const SEGMENTS: u32 = 128;
+            let mut d = String::new();
+            for i in 0..SEGMENTS {
+                let t = f64::from(i) / f64::from(SEGMENTS) * std::f64::consts::TAU;
+                let (sin, cos) = t.sin_cos();
+                let x = a + a * cos.signum() * cos.abs().sqrt();
+                let y = b + b * sin.signum() * sin.abs().sqrt();
+                write!(d, "{}{} {}", if i == 0 { 'M' } else { 'L' }, round(x), round(y)).unwrap();
+            }
+            format!("<path d=\"{}Z\"{}/>", d, attributes)
+        }
+        Mask::Rounded(_) => format!(
+            "<rect width=\"{}\" height=\"{}\" rx=\"{}\"{}/>",
+            width,
+            height,
+            round(mask.radius(width, height).unwrap_or(0.0)),
+            attributes
+        ),
+    };
+    Some(outline)
+}
+
+/// Rounds to three decimals, which is plenty for pixel coordinates.
+fn round(v: f64) -> f64 {
+    (v * 1000.0).round() / 1000.0
+}
+
+fn fill(color: Rgba<u8>) -> String {
+    let mut attributes = format!(" fill=\"#{:02x}{:02x}{:02x}\"", color[0], color[1], color[2]);
+    if color[3] != 255 {
+        write!(attributes, " fill-opacity=\"{:.3}\"", f32::from(color[3]) / 255.0).unwrap();
+    }
+    attributes
+}
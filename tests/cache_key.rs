@@ -0,0 +1,43 @@
+use identicon_generator::hash::Key;
+use identicon_generator::normalize::Case;
+use identicon_generator::render::Padding;
+use identicon_generator::IdenticonBuilder;
+
+fn key(builder: IdenticonBuilder, name: &str) -> String {
+    builder.build().unwrap().cache_key(name)
+}
+
+#[test]
+fn equivalent_spellings_share_a_key() {
+    let padded = |padding: &str| IdenticonBuilder::new().padding(padding.parse::<Padding>().unwrap());
+    assert_eq!(key(padded("10"), "xoltia"), key(padded("10px 10px"), "xoltia"));
+    assert_eq!(key(IdenticonBuilder::new(), "xoltia"), key(IdenticonBuilder::new().resolution(200), "xoltia"));
+
+    let lower = IdenticonBuilder::new().case(Case::Lower);
+    assert_eq!(key(lower.clone(), "Xoltia"), key(lower, "xoltia"));
+    assert_ne!(key(IdenticonBuilder::new(), "Xoltia"), key(IdenticonBuilder::new(), "xoltia"));
+}
+
+#[test]
+fn every_parameter_is_part_of_the_key() {
+    let base = key(IdenticonBuilder::new(), "xoltia");
+    let variants = [
+        IdenticonBuilder::new().grid_size(6),
+        IdenticonBuilder::new().resolution(100),
+        IdenticonBuilder::new().padding(1),
+        IdenticonBuilder::new().colors(3),
+        IdenticonBuilder::new().symmetrical(false),
+        IdenticonBuilder::new().key(Key::new("1", "secret")),
+    ];
+    for variant in &variants {
+        assert_ne!(key(variant.clone(), "xoltia"), base, "{:?}", variant);
+    }
+}
+
+#[test]
+fn replacing_a_secret_changes_the_key() {
+    let keyed = |secret: &str| IdenticonBuilder::new().key(Key::new("1", secret));
+    assert_eq!(key(keyed("old"), "xoltia"), key(keyed("old"), "xoltia"));
+    assert_ne!(key(keyed("old"), "xoltia"), key(keyed("new"), "xoltia"));
+    assert!(!key(keyed("old"), "xoltia").contains("old"));
+}
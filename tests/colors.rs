@@ -0,0 +1,53 @@
+use identicon_generator::grid::{self, Symmetry};
+use identicon_generator::IdenticonBuilder;
+
+/// How often each cell value occurs among every possible run of bits for a
+/// single cell.
+fn exhaustive_counts(colors: u8) -> Vec<usize> {
+    let width = grid::bits_per_cell(colors);
+    let mut counts = vec![0; usize::from(colors)];
+    for pattern in 0u32..1 << width {
+        let bits = (0..width).map(|i| (pattern >> i) & 1 == 1);
+        let values: Vec<u8> = grid::cell_values(bits, colors).collect();
+        assert_eq!(values.len(), 1);
+        counts[usize::from(values[0])] += 1;
+    }
+    counts
+}
+
+#[test]
+fn half_of_all_bit_patterns_are_empty() {
+    assert_eq!(exhaustive_counts(2), [1, 1]);
+    assert_eq!(exhaustive_counts(3), [2, 1, 1]);
+    assert_eq!(exhaustive_counts(4), [64, 22, 21, 21]);
+}
+
+#[test]
+fn cells_of_real_names_are_spread_evenly() {
+    for &colors in &[2u8, 3, 4] {
+        let identicon = IdenticonBuilder::new()
+            .grid_size(5)
+            .symmetry(Symmetry::None)
+            .colors(colors)
+            .build()
+            .unwrap();
+        let mut counts = vec![0usize; usize::from(colors)];
+        for i in 0..2000 {
+            let grid = identicon.grid(&format!("user{}", i)).unwrap();
+            for y in 0..5 {
+                for x in 0..5 {
+                    counts[usize::from(grid.cell(x, y).unwrap())] += 1;
+                }
+            }
+        }
+
+        let total = counts.iter().sum::<usize>() as f64;
+        let empty = counts[0] as f64 / total;
+        assert!((empty - 0.5).abs() < 0.02, "{} colours: {:?}", colors, counts);
+        let fill_share = 0.5 / f64::from(colors - 1);
+        for &count in &counts[1..] {
+            let share = count as f64 / total;
+            assert!((share - fill_share).abs() < 0.02, "{} colours: {:?}", colors, counts);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Helpers shared by the integration tests.
+
+use identicon_generator::color::Background;
+use identicon_generator::IdenticonBuilder;
+use image::Rgba;
+
+/// An opaque background no fill colour is likely to match.
+pub const BACKGROUND: Rgba<u8> = Rgba([10, 20, 30, 255]);
+
+/// A builder for a square grid of `grid_size` cells on a canvas of
+/// `resolution` pixels, over [`BACKGROUND`].
+pub fn builder(grid_size: u32, resolution: u32) -> IdenticonBuilder {
+    IdenticonBuilder::new()
+        .grid_size(grid_size)
+        .resolution(resolution)
+        .background(Background::Color(BACKGROUND))
+}
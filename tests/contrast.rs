@@ -0,0 +1,41 @@
+use identicon_generator::color::{contrast_ratio, ensure_contrast, rgb_to_hsl, Contrast};
+use image::Rgba;
+
+const PALE_YELLOW: Rgba<u8> = Rgba([255, 255, 153, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const TRANSPARENT: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+#[test]
+fn pale_yellow_on_white_reaches_the_ratio() {
+    assert!(contrast_ratio(PALE_YELLOW, WHITE) < 1.1);
+    for &(contrast, ratio) in &[(Contrast::Aa, 4.5), (Contrast::Aaa, 7.0)] {
+        let adjusted = ensure_contrast(PALE_YELLOW, WHITE, contrast);
+        assert!(contrast_ratio(adjusted, WHITE) >= ratio, "{:?} gave {:?}", contrast, adjusted);
+        // Only the lightness is adjusted, and no further than needed.
+        let (hue, _, _) = rgb_to_hsl(adjusted);
+        assert!((hue - rgb_to_hsl(PALE_YELLOW).0).abs() < 0.01, "{:?} gave {:?}", contrast, adjusted);
+        assert!(contrast_ratio(adjusted, WHITE) < ratio + 0.5, "{:?} gave {:?}", contrast, adjusted);
+    }
+}
+
+#[test]
+fn colours_that_already_contrast_are_kept() {
+    assert_eq!(ensure_contrast(PALE_YELLOW, WHITE, Contrast::Off), PALE_YELLOW);
+    assert_eq!(ensure_contrast(PALE_YELLOW, BLACK, Contrast::Aaa), PALE_YELLOW);
+    assert_eq!(ensure_contrast(BLACK, WHITE, Contrast::Aaa), BLACK);
+}
+
+#[test]
+fn transparent_backgrounds_are_checked_against_white_and_black() {
+    let worst = |color: Rgba<u8>| contrast_ratio(color, WHITE).min(contrast_ratio(color, BLACK));
+
+    let aa = ensure_contrast(PALE_YELLOW, TRANSPARENT, Contrast::Aa);
+    assert!(worst(aa) >= 4.5, "{:?} has {}", aa, worst(aa));
+
+    // No colour reaches 7:1 against both, so the best compromise is used.
+    for &color in &[PALE_YELLOW, BLACK, WHITE] {
+        let aaa = ensure_contrast(color, TRANSPARENT, Contrast::Aaa);
+        assert!(worst(aaa) > 4.5 && worst(aaa) < 4.6, "{:?} has {}", aaa, worst(aaa));
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::BACKGROUND;
+use identicon_generator::grid::Symmetry;
+use identicon_generator::render::Gap;
+use identicon_generator::IdenticonBuilder;
+use image::GenericImageView;
+
+fn builder(grid_size: u32, resolution: u32, gap: &str) -> IdenticonBuilder {
+    common::builder(grid_size, resolution).gap(gap.parse::<Gap>().unwrap())
+}
+
+#[test]
+fn fractions_are_of_the_cell_side() {
+    let cases = [(5, 200, "0.5", 0.5, 14), (5, 200, "50%", 0.5, 14), (4, 100, "0.25", 0.25, 5), (7, 300, "0.1", 0.1, 4)];
+    for &(grid_size, resolution, gap, fraction, pixels) in &cases {
+        let layout = *builder(grid_size, resolution, gap).build().unwrap().layout();
+        let (cell, _) = layout.cell_size();
+        assert_eq!(layout.gap, pixels, "gap={} on {}x{}", gap, grid_size, resolution);
+        assert!((f64::from(layout.gap) - fraction * f64::from(cell)).abs() <= 1.0, "gap {} for cells of {}", layout.gap, cell);
+    }
+}
+
+#[test]
+fn fractions_use_the_shorter_cell_side() {
+    let identicon = IdenticonBuilder::new()
+        .columns(5)
+        .rows(5)
+        .width(400)
+        .height(200)
+        .gap(Gap::Fraction(0.5))
+        .build()
+        .unwrap();
+    let layout = identicon.layout();
+    assert_eq!(layout.gap, 14);
+}
+
+#[test]
+fn gaps_are_left_blank_in_pixels() {
+    // 4 cells of 20 pixels with gaps of 10 pixels fill 110 pixels exactly.
+    let identicon = builder(4, 110, "0.5")
+        .symmetry(Symmetry::None)
+        .build()
+        .unwrap();
+    assert_eq!(identicon.layout().gap, 10);
+    assert_eq!(identicon.layout().cell_size(), (20, 20));
+
+    let img = identicon.image("xoltia").unwrap();
+    for (x, y, pixel) in img.pixels() {
+        if x % 30 >= 20 || y % 30 >= 20 {
+            assert_eq!(pixel, BACKGROUND, "pixel {}, {} is in a gap", x, y);
+        }
+    }
+}
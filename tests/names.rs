@@ -0,0 +1,49 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use identicon_generator::color::{ColorModel, Contrast};
+use identicon_generator::grid::Symmetry;
+use identicon_generator::hash::Algorithm;
+use identicon_generator::normalize::Case;
+use identicon_generator::render::Filter;
+use identicon_generator::shape::{Mask, Shape};
+
+/// Checks that every name in `names` parses to the value listed with it,
+/// and that the canonical name of each value is listed.
+fn assert_round_trips<T>(names: &[(&str, T)], name: impl Fn(T) -> &'static str)
+where
+    T: FromStr + Copy + PartialEq + Debug,
+    T::Err: Debug,
+{
+    for &(spelling, value) in names {
+        assert_eq!(spelling.parse::<T>().unwrap(), value, "{:?}", spelling);
+        assert!(names.contains(&(name(value), value)), "{:?} is not listed", name(value));
+    }
+    assert!("unlisted".parse::<T>().is_err());
+}
+
+#[test]
+fn every_listed_name_parses() {
+    assert_round_trips(Algorithm::NAMES, Algorithm::name);
+    assert_round_trips(ColorModel::NAMES, ColorModel::name);
+    assert_round_trips(Contrast::NAMES, Contrast::name);
+    assert_round_trips(Symmetry::NAMES, Symmetry::name);
+    assert_round_trips(Shape::NAMES, Shape::name);
+    assert_round_trips(Filter::NAMES, Filter::name);
+    assert_round_trips(Case::NAMES, |case| match case {
+        Case::Preserve => "preserve",
+        Case::Lower => "lower",
+    });
+    for &(spelling, mask) in Mask::NAMES {
+        assert_eq!(spelling.parse::<Mask>().unwrap(), mask);
+    }
+}
+
+#[test]
+fn names_ignore_case_except_for_colour_models() {
+    assert_eq!("SHA256".parse::<Algorithm>().unwrap(), Algorithm::Sha256);
+    assert_eq!("Yes".parse::<Symmetry>().unwrap(), Symmetry::Horizontal);
+    assert_eq!("Circle".parse::<Mask>().unwrap(), Mask::Circle);
+    assert!("rounded:10PX".parse::<Mask>().is_ok());
+    assert!("HSL".parse::<ColorModel>().is_err());
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::BACKGROUND;
+use identicon_generator::render::{Insets, Length, Padding};
+use identicon_generator::{Error, IdenticonBuilder};
+use image::GenericImageView;
+
+fn builder(grid_size: u32, resolution: u32, symmetrical: bool) -> IdenticonBuilder {
+    common::builder(grid_size, resolution).symmetrical(symmetrical)
+}
+
+/// Renders `name` with and without `padding` and checks that the grid is
+/// drawn unchanged inside the padding, and that nothing but the background
+/// is drawn outside of it.
+fn assert_padded_matches_unpadded(builder: IdenticonBuilder, padding: Padding, name: &str) {
+    let unpadded = builder.clone().build().unwrap().image(name).unwrap();
+    let padded = builder.padding(padding).build().unwrap();
+    let Insets { top, right, bottom, left } = padded.layout().padding;
+    let padded = padded.image(name).unwrap();
+
+    let (width, height) = unpadded.dimensions();
+    assert_eq!(padded.dimensions(), (left + width + right, top + height + bottom));
+
+    for (x, y, pixel) in padded.pixels() {
+        let inside = (left..left + width).contains(&x) && (top..top + height).contains(&y);
+        if inside {
+            assert_eq!(pixel, unpadded.get_pixel(x - left, y - top), "pixel {}, {} of {}", x, y, name);
+        } else {
+            assert_eq!(pixel, BACKGROUND, "padding pixel {}, {} of {}", x, y, name);
+        }
+    }
+}
+
+#[test]
+fn uniform_padding_keeps_the_whole_grid() {
+    for &grid_size in &[3, 5, 6, 7] {
+        for &symmetrical in &[true, false] {
+            let builder = builder(grid_size, grid_size * 20, symmetrical);
+            assert_padded_matches_unpadded(builder, Padding::from(15), "xoltia");
+        }
+    }
+}
+
+#[test]
+fn padding_per_side_keeps_the_whole_grid() {
+    let padding = "3 11 7 19".parse().unwrap();
+    for name in &["xoltia", "alice", "bob"] {
+        assert_padded_matches_unpadded(builder(5, 100, true), padding, name);
+        assert_padded_matches_unpadded(builder(6, 120, false), padding, name);
+    }
+}
+
+#[test]
+fn padding_keeps_the_whole_grid_when_resampled() {
+    assert_padded_matches_unpadded(builder(7, 101, true), "5 9".parse().unwrap(), "xoltia");
+}
+
+#[test]
+fn padded_grid_is_mirrored_around_its_own_centre() {
+    let identicon = builder(5, 100, true).padding("0 0 0 40".parse::<Padding>().unwrap()).build().unwrap();
+    let img = identicon.image("xoltia").unwrap();
+    for y in 0..100 {
+        for x in 0..100 {
+            assert_eq!(img.get_pixel(40 + x, y), img.get_pixel(40 + 99 - x, y));
+        }
+    }
+}
+
+#[test]
+fn percentages_are_relative_to_the_grid_side() {
+    let identicon = IdenticonBuilder::new()
+        .width(400)
+        .height(200)
+        .padding("10%".parse::<Padding>().unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(identicon.layout().padding, Insets { top: 20, right: 40, bottom: 20, left: 40 });
+    assert_eq!(identicon.image("xoltia").unwrap().dimensions(), (480, 240));
+}
+
+#[test]
+fn shorthand() {
+    let px = Length::Pixels;
+    assert_eq!("4".parse().ok(), Some(Padding::uniform(px(4))));
+    assert_eq!(
+        "4 8".parse().ok(),
+        Some(Padding { top: px(4), right: px(8), bottom: px(4), left: px(8) })
+    );
+    assert_eq!(
+        "1px,2,3%,4".parse().ok(),
+        Some(Padding { top: px(1), right: px(2), bottom: Length::Percent(3.0), left: px(4) })
+    );
+    for invalid in &["", "1 2 3", "1 2 3 4 5", "-1", "150%", "ten"] {
+        assert!(invalid.parse::<Padding>().is_err(), "{:?} parsed", invalid);
+    }
+}
+
+#[test]
+fn padded_canvas_is_limited_to_the_maximum_resolution() {
+    let build = |resolution: u32, padding: u32| builder(5, resolution, true).padding(padding).build();
+
+    assert!(build(990, 5).is_ok());
+    for &(resolution, padding) in &[(1000, 1), (200, 20000), (200, u32::MAX), (u32::MAX, u32::MAX)] {
+        match build(resolution, padding) {
+            Err(Error::ResolutionTooLarge { side, padding: padded }) => {
+                assert_eq!((side, padded), (resolution, padding.saturating_mul(2)));
+            }
+            other => panic!("res {} and pad {} gave {:?}", resolution, padding, other.map(|_| ())),
+        }
+    }
+}
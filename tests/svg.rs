@@ -0,0 +1,116 @@
+use identicon_generator::grid::{Grid, Symmetry};
+use identicon_generator::svg;
+use identicon_generator::IdenticonBuilder;
+
+fn grid(rows: &[&[u8]]) -> Grid {
+    let cells = rows.iter().flat_map(|row| row.iter().copied());
+    Grid::from_cells(rows[0].len() as u32, rows.len() as u32, Symmetry::None, cells).unwrap()
+}
+
+/// Twice the signed area of `outline`, positive for clockwise loops as the
+/// y axis points down.
+fn area(outline: &[(u32, u32)]) -> i64 {
+    let n = outline.len();
+    (0..n)
+        .map(|i| {
+            let (x0, y0) = outline[i];
+            let (x1, y1) = outline[(i + 1) % n];
+            i64::from(x0) * i64::from(y1) - i64::from(x1) * i64::from(y0)
+        })
+        .sum()
+}
+
+/// The winding number of `outlines` around the centre of the cell at
+/// column `x`, row `y`.
+fn winding(outlines: &[Vec<(u32, u32)>], x: u32, y: u32) -> i32 {
+    let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+    let mut winding = 0;
+    for outline in outlines {
+        let n = outline.len();
+        for i in 0..n {
+            let (x0, y0) = outline[i];
+            let (x1, y1) = outline[(i + 1) % n];
+            let (fx, fy0, fy1) = (f64::from(x0), f64::from(y0), f64::from(y1));
+            if x0 == x1 && fx > px && fy0.min(fy1) < py && py < fy0.max(fy1) {
+                winding += if y1 > y0 { 1 } else { -1 };
+            }
+        }
+    }
+    winding
+}
+
+/// Checks that filling `outlines` with the `nonzero` rule covers exactly the
+/// cells of `grid` holding `value`, and that every edge is axis-aligned.
+fn assert_fills(grid: &Grid, value: u8, outlines: &[Vec<(u32, u32)>]) {
+    for outline in outlines {
+        let n = outline.len();
+        for i in 0..n {
+            let ((x0, y0), (x1, y1)) = (outline[i], outline[(i + 1) % n]);
+            assert!(x0 == x1 || y0 == y1, "diagonal edge in {:?}", outline);
+        }
+    }
+    for y in 0..grid.rows() {
+        for x in 0..grid.columns() {
+            let filled = grid.cell(i64::from(x), i64::from(y)) == Some(value);
+            assert_eq!(winding(outlines, x, y) != 0, filled, "cell {}, {} of {:?}", x, y, outlines);
+        }
+    }
+}
+
+#[test]
+fn a_single_cell_is_a_square() {
+    let grid = grid(&[&[0, 0, 0], &[0, 1, 0], &[0, 0, 0]]);
+    let outlines = svg::outlines(&grid, 1);
+    assert_eq!(outlines, [vec![(1, 1), (2, 1), (2, 2), (1, 2)]]);
+    assert!(area(&outlines[0]) > 0);
+    assert!(svg::outlines(&grid, 2).is_empty());
+
+    let layout = *IdenticonBuilder::new().grid_size(3).resolution(30).padding(5).build().unwrap().layout();
+    assert_eq!(svg::path_data(&outlines, &layout), "M15 15H25V25H15Z");
+}
+
+#[test]
+fn adjacent_cells_merge_into_one_outline() {
+    let grid = grid(&[&[1, 1, 0], &[0, 1, 0], &[0, 1, 1]]);
+    let outlines = svg::outlines(&grid, 1);
+    assert_eq!(outlines, [vec![(0, 0), (2, 0), (2, 2), (3, 2), (3, 3), (1, 3), (1, 1), (0, 1)]]);
+    assert_eq!(area(&outlines[0]), 2 * 5);
+    assert_fills(&grid, 1, &outlines);
+}
+
+#[test]
+fn holes_wind_the_opposite_way() {
+    let grid = grid(&[&[1, 1, 1, 1], &[1, 0, 0, 1], &[1, 1, 1, 1]]);
+    let outlines = svg::outlines(&grid, 1);
+    assert_eq!(outlines.len(), 2);
+
+    let mut areas: Vec<i64> = outlines.iter().map(|outline| area(outline)).collect();
+    areas.sort();
+    assert_eq!(areas, [-2 * 2, 2 * 12]);
+    assert_fills(&grid, 1, &outlines);
+    assert_fills(&grid, 0, &svg::outlines(&grid, 0));
+}
+
+#[test]
+fn cells_touching_at_a_corner_fill_only_themselves() {
+    for rows in [[[1, 0], [0, 1]], [[0, 1], [1, 0]]] {
+        let rows: Vec<&[u8]> = rows.iter().map(|row| &row[..]).collect();
+        let grid = grid(&rows);
+        let outlines = svg::outlines(&grid, 1);
+        let total: i64 = outlines.iter().map(|outline| area(outline)).sum();
+        assert_eq!(total, 2 * 2, "{:?}", outlines);
+        assert!(outlines.iter().all(|outline| area(outline) > 0), "{:?}", outlines);
+        assert_fills(&grid, 1, &outlines);
+    }
+}
+
+#[test]
+fn every_colour_fills_its_own_cells() {
+    let identicon = IdenticonBuilder::new().grid_size(7).colors(4).symmetry(Symmetry::None).build().unwrap();
+    for name in &["xoltia", "alice", "bob", "identicon"] {
+        let grid = identicon.grid(name).unwrap();
+        for value in 1..=3 {
+            assert_fills(&grid, value, &svg::outlines(&grid, value));
+        }
+    }
+}